@@ -0,0 +1,275 @@
+/* An opinionated line wrapper for markdown files.
+Copyright (C) 2023  Torsten Long
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use crate::ignore::IgnoreByHtmlComment;
+use crate::parse::CharRange;
+use crate::ranges::{TextRange, WrapType};
+use crate::trace_log;
+
+/// Normalise blank lines and trailing whitespace in all verbatim ranges, i.e. outside of the text
+/// that is wrapped anyway. Top-level block elements that directly follow each other are separated
+/// by a blank line. Runs of blank lines are collapsed to at most "max_blank_lines" lines, but at
+/// least one blank line is always kept. Trailing whitespace is removed unless it is a hard line
+/// break. Code blocks, HTML blocks, and ignored sections are never modified. The returned ranges
+/// refer to the returned text.
+pub fn normalize_blank_lines(
+    ranges: Vec<TextRange>,
+    text: &str,
+    max_blank_lines: usize,
+) -> (String, Vec<TextRange>) {
+    let max_blank_lines = max_blank_lines.max(1);
+    let mut protected = vec![];
+    let mut hard_breaks = vec![];
+    let mut blocks = vec![];
+    let mut depth: usize = 0;
+    let mut ignore = IgnoreByHtmlComment::new();
+    let mut ignore_start = 0;
+
+    for (event, range) in Parser::new_ext(
+        text,
+        Options::ENABLE_TABLES | Options::ENABLE_DEFINITION_LIST,
+    )
+    .into_offset_iter()
+    {
+        match event {
+            Event::Start(tag) => {
+                if matches!(tag, Tag::CodeBlock(..) | Tag::HtmlBlock) {
+                    protected.push(range.clone());
+                }
+                if depth == 0 {
+                    blocks.push(range);
+                }
+                depth += 1;
+            }
+            Event::End(tag) => {
+                depth = depth.saturating_sub(1);
+                // The range of a list might include link definitions following it, that of its
+                // items does not.
+                if depth == 1
+                    && tag == TagEnd::Item
+                    && let Some(list) = blocks.last_mut()
+                {
+                    list.end = range.end;
+                }
+            }
+            Event::HardBreak => hard_breaks.push(range),
+            Event::Html(html) => {
+                let was_ignored = ignore.should_be_ignored();
+                ignore.process_html(&html);
+                match (was_ignored, ignore.should_be_ignored()) {
+                    (false, true) => ignore_start = range.start,
+                    (true, false) => protected.push(ignore_start..range.end),
+                    _ => {}
+                }
+            }
+            // Some top-level elements, e.g. thematic breaks, do not have a start event.
+            _ if depth == 0 => blocks.push(range),
+            _ => {}
+        }
+    }
+    if ignore.should_be_ignored() {
+        protected.push(ignore_start..text.len());
+    }
+    let separators = find_missing_separators(text, &blocks)
+        .into_iter()
+        // A separator right at the start of a protected block, e.g. a code block, is still needed.
+        .filter(|pos| !protected.iter().any(|el| el.start < *pos && *pos < el.end))
+        .collect::<Vec<_>>();
+
+    let mut result = String::with_capacity(text.len());
+    let mut new_ranges = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let start = result.len();
+        if range.wrap == WrapType::Verbatim {
+            let lines = Lines {
+                protected: &protected,
+                hard_breaks: &hard_breaks,
+                separators: &separators,
+                max_blank_lines,
+            };
+            result.push_str(&lines.normalize(text, &range.range));
+        } else {
+            result.push_str(&text[range.range.clone()]);
+        }
+        new_ranges.push(TextRange {
+            wrap: range.wrap,
+            range: CharRange {
+                start,
+                end: result.len(),
+            },
+        });
+    }
+
+    (result, new_ranges)
+}
+
+/// Find the starts of those lines before which a blank line has to be inserted to separate them
+/// from the top-level block element ending on the line right before.
+fn find_missing_separators(text: &str, blocks: &[CharRange]) -> Vec<usize> {
+    blocks
+        .windows(2)
+        .filter_map(|pair| {
+            let prev_end = line_end(text, pair[0].end.saturating_sub(1));
+            let next_start = text[..pair[1].start].rfind('\n').map_or(0, |el| el + 1);
+            // Some blocks, e.g. lists, include trailing blank lines. Thus, check the previous line.
+            let prev_line_start = text[..next_start.saturating_sub(1)]
+                .rfind('\n')
+                .map_or(0, |el| el + 1);
+            let has_blank_line = text[prev_line_start..next_start].trim().is_empty();
+            // Link reference definitions cause no events. Blocks around them are left alone.
+            (prev_end == next_start && !has_blank_line).then(|| {
+                trace_log!("separating block at {} by a blank line", next_start);
+                next_start
+            })
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Determine the start of the line following the one that "pos" is in.
+fn line_end(text: &str, pos: usize) -> usize {
+    text[pos..].find('\n').map_or(text.len(), |el| pos + el + 1)
+}
+
+/// Lines provides what is needed to normalise the lines of a verbatim range.
+struct Lines<'a> {
+    protected: &'a [CharRange],
+    hard_breaks: &'a [CharRange],
+    separators: &'a [usize],
+    max_blank_lines: usize,
+}
+
+impl Lines<'_> {
+    fn normalize(&self, text: &str, range: &CharRange) -> String {
+        let mut result = String::new();
+        let mut blank_lines = 0;
+        let mut line_start = range.start;
+
+        for line in text[range.clone()].split_inclusive('\n') {
+            let line_range = CharRange {
+                start: line_start,
+                end: line_start + line.len(),
+            };
+            line_start = line_range.end;
+
+            if self.separators.contains(&line_range.start) {
+                result.push('\n');
+            }
+            let is_protected = self
+                .protected
+                .iter()
+                .any(|el| el.start < line_range.end && line_range.start < el.end);
+            if is_protected || !line.ends_with('\n') {
+                // Partial lines continue in a range that is wrapped.
+                result.push_str(line);
+                blank_lines = 0;
+                continue;
+            }
+
+            let is_full_line = line_range.start == 0 || text[..line_range.start].ends_with('\n');
+            let is_hard_break = self
+                .hard_breaks
+                .iter()
+                .any(|el| el.contains(&(line_range.end - 1)));
+            let ending = if line.ends_with("\r\n") { "\r\n" } else { "\n" };
+            let content = &line[..line.len() - ending.len()];
+
+            if is_full_line && content.trim().is_empty() {
+                blank_lines += 1;
+                if blank_lines <= self.max_blank_lines {
+                    result.push_str(ending);
+                } else {
+                    trace_log!("removing blank line at {}", line_range.start);
+                }
+            } else {
+                blank_lines = 0;
+                if is_hard_break {
+                    result.push_str(content);
+                } else {
+                    result.push_str(content.trim_end());
+                }
+                result.push_str(ending);
+            }
+        }
+        if self.separators.contains(&range.end) && range.end == line_start && range.end > 0 {
+            result.push('\n');
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::features::FeatureCfg;
+    use crate::parse::parse_markdown;
+    use crate::ranges::fill_markdown_ranges;
+    use crate::template::TemplateDelimiters;
+
+    fn normalize(text: &str, max_blank_lines: usize) -> String {
+        let parsed = parse_markdown(
+            text,
+            &FeatureCfg::default().parse_cfg,
+            &TemplateDelimiters::default(),
+        );
+        let filled = fill_markdown_ranges(parsed, text);
+        let (result, ranges) = normalize_blank_lines(filled, text, max_blank_lines);
+        // The ranges have to cover the entire result.
+        let covered = ranges.iter().map(|el| &result[el.range.clone()]);
+        assert_eq!(result, covered.collect::<String>());
+        result
+    }
+
+    #[test]
+    fn collapsing_blank_lines_and_removing_trailing_whitespace() {
+        let text = "# Title   \n\n\n\nSome text.\n  \n\n\n- item\n\n\n\n  ```\n  code  \n\n\n\n  ```\n\n\n\n<pre>\n\n\n\n</pre>\n";
+        let expected = "# Title\n\nSome text.\n\n- item\n\n  ```\n  code  \n\n\n\n  ```\n\n<pre>\n\n\n\n</pre>\n";
+        assert_eq!(expected, normalize(text, 1));
+        let expected = "# Title\n\n\nSome text.\n\n\n- item\n\n\n  ```\n  code  \n\n\n\n  ```\n\n\n<pre>\n\n\n\n</pre>\n";
+        assert_eq!(expected, normalize(text, 2));
+        assert_eq!(normalize(text, 1), normalize(text, 0));
+    }
+
+    #[test]
+    fn separating_block_elements() {
+        let text = "# Title\nSome text.\n***\n## Sub\n- item\n- item\n\n```\ncode\n```\n> quote\n";
+        let expected = "# Title\n\nSome text.\n\n***\n\n## Sub\n\n- item\n- item\n\n```\ncode\n```\n\n> quote\n";
+        assert_eq!(expected, normalize(text, 1));
+        let text = "Some text.\n```\ncode\n```\n";
+        let expected = "Some text.\n\n```\ncode\n```\n";
+        assert_eq!(expected, normalize(text, 1));
+        // Formatting might turn text into a link definition. The next run must not change that.
+        let text = "Some text.\n\n[a]:\nb\nMore text.\n";
+        assert_eq!(text, normalize(text, 1));
+        let text = "- item\n\n[a]: b\n[c]:\nd\nMore text.\n";
+        assert_eq!(text, normalize(text, 1));
+    }
+
+    #[test]
+    fn keeping_ignored_sections() {
+        let text = "<!-- mdslw-ignore-start -->\nText  \n\n\n\n# Heading\nText\n<!-- mdslw-ignore-end -->\n";
+        assert_eq!(text, normalize(text, 1));
+    }
+
+    #[test]
+    fn keeping_hard_breaks() {
+        let text = "<span>a</span>  \nb\n";
+        assert_eq!(text, normalize(text, 1));
+    }
+}
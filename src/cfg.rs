@@ -19,7 +19,7 @@ use std::fmt;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use clap::{Parser, ValueEnum, builder::OsStr};
+use clap::{Parser, Subcommand, ValueEnum, builder::OsStr};
 use clap_complete::Shell;
 use serde::{Deserialize, Serialize};
 
@@ -61,7 +61,7 @@ impl<T> ValueWOrigin<T> {
     ///   - Otherwise, return the value in the "Default".
     ///     In that case, neither has the user specified that option on the command line, nor is it
     ///     contained in any config file.
-    fn resolve(&self, other: Option<T>) -> T
+    pub fn resolve(&self, other: Option<T>) -> T
     where
         T: Clone,
     {
@@ -145,6 +145,43 @@ impl fmt::Display for Case {
     }
 }
 
+#[derive(Serialize, Deserialize, Copy, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum WrapMode {
+    Greedy,
+    Sembr,
+    Optimal,
+}
+
+impl FromStr for WrapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "greedy" => Ok(Self::Greedy),
+            "sembr" => Ok(Self::Sembr),
+            "optimal" => Ok(Self::Optimal),
+            _ => Err(String::from("possible values: greedy, sembr, optimal")),
+        }
+    }
+}
+
+impl fmt::Display for WrapMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Greedy => {
+                write!(f, "greedy")
+            }
+            Self::Sembr => {
+                write!(f, "sembr")
+            }
+            Self::Optimal => {
+                write!(f, "optimal")
+            }
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum ReportMode {
     None,
@@ -189,8 +226,12 @@ pub struct CliArgs {
     /// be {n}   taken into account. See here for all languages:
     /// {n}   https://github.com/unicode-org/cldr-json/tree/main/cldr-json/cldr-segments-full/segments
     /// {n}   Use "none" to disable.
-    /// Supported languages are: de en es fr it. Use "ac" for "author's choice",{n}   a list
-    /// for the Enlish language defined by this tool's author.
+    /// Supported languages are: de en es fr it pt ru. Use "ac" for "author's choice",{n}   a list
+    /// for the Enlish language defined by this tool's author. Other languages, e.g. nl or sv,
+    /// {n}   have no suppression words in CLDR and need to be configured via --suppressions.
+    /// {n}   Use "auto" to detect the language per document from a "lang" or "language"
+    /// frontmatter key,{n}   a file name like "README.de.md", or a directory like "docs/fr/", in
+    /// that order.
     #[arg(short, long, env = "MDSLW_LANG", default_value = "ac\u{200b}")]
     pub lang: ValueWOrigin<String>,
     /// Space-separated list of words that end in one of END_MARKERS but that should not be
@@ -240,9 +281,64 @@ pub struct CliArgs {
     ///         definition,{n}       i.e. `[link](url)` becomes `[link][def]` and `[def]: url`
     /// {n}   * keep-colon-fences => recognise Sphinx/MyST colon fenced blocks, do not modify lines
     ///         containing {n}       directive parameters but auto-format other fenced text
+    /// {n}   * atomic-code-spans => never wrap inside inline code spans and ignore end markers
+    ///         in them,{n}       i.e. treat each code span like a single word
+    /// {n}   * format-directive-fences => format the text in MyST admonitions using backtick
+    ///         fences,{n}       e.g. ```{note}, but keep the fences and option lines as they are
+    /// {n}   * format-admonitions => format the indented bodies of Python-Markdown and MkDocs
+    ///         admonitions,{n}       e.g. `!!! note`, but keep their header lines as they are
+    /// {n}   * mdx => keep import and export statements of MDX documents as they are, treat JSX
+    ///         tags{n}       and expressions as atomic, e.g. `{props.x}`, and discover .mdx files
+    /// {n}   * wiki-links => treat wiki links and embeds as atomic, e.g. `[[Some Page|alias]]`,
+    ///         and{n}       keep embeds on their own lines as they are, e.g. `![[image.png]]`
+    /// {n}   * pandoc => treat Pandoc citations and bracketed spans as atomic, keep line blocks
+    ///         as{n}       they are, and handle fenced divs like keep-colon-fences does
+    /// {n}   * quarto => support Quarto and R Markdown documents like pandoc does, treat inline
+    ///         code{n}       chunks as atomic, e.g. `r x`, and also discover .qmd and .Rmd files
+    /// {n}   * tasklists => detect task list items, e.g. `- [ ] task`, and indent wrapped
+    ///         lines{n}       relative to the text after the checkbox
+    /// {n}   * heading-attributes => detect attributes of headings, e.g. `# Heading {#id}`
+    /// {n}   * strikethrough => detect strikethrough text, e.g. `~~text~~`, and treat it as
+    ///         atomic
+    /// {n}   * normalize-lists => renumber ordered lists, use the same bullet marker on each
+    ///         nesting{n}       level, and re-indent list items to match their new markers
+    /// {n}   * align-tables => pad the cells of pipe tables such that columns line up and
+    ///         normalise{n}       their delimiter rows, but never break cells across lines
+    /// {n}   * normalize-headings => convert setext headings to ATX headings, remove closing
+    ///         hashes,{n}       and collapse whitespace in heading texts
+    /// {n}   * normalize-blank-lines => separate block elements by blank lines, collapse runs
+    ///         of blank{n}       lines to at most MAX_BLANK_LINES, and remove trailing
+    ///         whitespace outside of paragraphs
+    /// {n}   * prune-link-defs => remove link definitions that are not referenced anywhere in
+    ///         the document{n}       and fail for references without link definitions when
+    ///         checking
     /// {n}  .
     #[arg(long, env = "MDSLW_FEATURES", default_value = "\u{200b}")]
     pub features: ValueWOrigin<String>,
+    /// How to wrap sentences that are longer than MAX_WIDTH. Possible values:
+    /// {n}   * "greedy" => put as many words as possible on each line
+    /// {n}   * "sembr" => prefer line breaks after commas, semicolons, and em dashes as well as
+    ///         before{n}       conjunctions according to the semantic line breaks specification,
+    ///         use "greedy" if{n}       there is no such position
+    /// {n}   * "optimal" => minimise the raggedness of all lines of a sentence and avoid a
+    ///         single word{n}       on the last line
+    /// {n}  .
+    #[arg(long, env = "MDSLW_WRAP_MODE", default_value = "greedy\u{200b}")]
+    pub wrap_mode: ValueWOrigin<WrapMode>,
+    /// The maximum width of a link text that is kept on a single line. Longer link texts are
+    /// wrapped{n}   like any other text while the link target is never split. A value of 0 keeps
+    /// all link texts{n}   on a single line. Has no effect with the keep-spaces-in-links feature.
+    #[arg(long, env = "MDSLW_MAX_LINK_TEXT_WIDTH", default_value = "0\u{200b}")]
+    pub max_link_text_width: ValueWOrigin<usize>,
+    /// Space-separated list of delimiter pairs that enclose template code, e.g. shortcodes.
+    /// Template code{n}   is never wrapped. Specify a pair as "OPEN,CLOSE", e.g. "<%,%>", or
+    /// use one of the presets{n}   hugo, jekyll, liquid, jinja, or markdoc.
+    #[arg(long, env = "MDSLW_TEMPLATE_DELIMITERS", default_value = "\u{200b}")]
+    pub template_delimiters: ValueWOrigin<String>,
+    /// The maximum number of consecutive blank lines outside of code blocks. Only used with the
+    /// {n}   normalize-blank-lines feature. A value of 0 is treated like 1.
+    #[arg(long, env = "MDSLW_MAX_BLANK_LINES", default_value = "1\u{200b}")]
+    pub max_blank_lines: ValueWOrigin<usize>,
     /// Output shell completion file for the given shell to stdout and exit.{n}  .
     #[arg(value_enum, long, env = "MDSLW_COMPLETION")]
     pub completion: Option<Shell>,
@@ -277,6 +373,20 @@ pub struct CliArgs {
     /// further.
     #[arg(short, long, action = clap::ArgAction::Count)]
     pub verbose: u8,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Scan markdown files for words that end in one of END_MARKERS but that are usually followed
+    /// by{n}a lowercase word or a number. Output them ranked by frequency as candidate
+    /// suppressions in the{n}format of a config file. Words that are already suppressed are not
+    /// reported. Reads from stdin{n}if no PATHS are given.
+    SuggestSuppressions {
+        /// Paths to files or directories that shall be scanned.
+        paths: Vec<PathBuf>,
+    },
 }
 
 #[derive(Debug, PartialEq)]
@@ -291,6 +401,10 @@ pub struct PerFileCfg {
     pub upstream_separator: String,
     pub case: Case,
     pub features: String,
+    pub wrap_mode: WrapMode,
+    pub max_link_text_width: usize,
+    pub template_delimiters: String,
+    pub max_blank_lines: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -306,6 +420,10 @@ pub struct CfgFile {
     pub upstream_separator: Option<String>,
     pub case: Option<Case>,
     pub features: Option<String>,
+    pub wrap_mode: Option<WrapMode>,
+    pub max_link_text_width: Option<usize>,
+    pub template_delimiters: Option<String>,
+    pub max_blank_lines: Option<usize>,
 }
 
 impl CfgFile {
@@ -335,6 +453,10 @@ impl CfgFile {
         merge_field!(upstream_separator);
         merge_field!(case);
         merge_field!(features);
+        merge_field!(wrap_mode);
+        merge_field!(max_link_text_width);
+        merge_field!(template_delimiters);
+        merge_field!(max_blank_lines);
 
         fully_defined
     }
@@ -351,6 +473,10 @@ impl CfgFile {
             upstream_separator: None,
             case: None,
             features: None,
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         }
     }
 }
@@ -372,7 +498,7 @@ impl Default for CfgFile {
             ($($names:ident)*) => { merge_fields!(@ $($names)* | ) };
         }
 
-        merge_fields!(max_width end_markers lang suppressions ignores upstream_command upstream upstream_separator case features)
+        merge_fields!(max_width end_markers lang suppressions ignores upstream_command upstream upstream_separator case features wrap_mode max_link_text_width template_delimiters max_blank_lines)
     }
 }
 
@@ -403,7 +529,7 @@ where
         ($($names:ident)*) => { merge_fields!(@ $($names)* | ) };
     }
 
-    let result = merge_fields!(max_width end_markers lang suppressions ignores upstream_command upstream upstream_separator case features);
+    let result = merge_fields!(max_width end_markers lang suppressions ignores upstream_command upstream upstream_separator case features wrap_mode max_link_text_width template_delimiters max_blank_lines);
     log::debug!("merged configuration: {:?}", result);
     result
 }
@@ -426,6 +552,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: None,
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
         let other_cfg = CfgFile {
             max_width: None,
@@ -438,6 +568,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: Some("feature".into()),
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
 
         let fully_defined = main_cfg.merge_with(&other_cfg);
@@ -454,6 +588,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: Some("feature".into()),
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
 
         assert_eq!(expected_cfg, main_cfg);
@@ -472,6 +610,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: None,
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
         let other_cfg = CfgFile {
             max_width: Some(20),
@@ -484,6 +626,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: None,
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
         assert_ne!(main_cfg, other_cfg);
 
@@ -501,6 +647,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: None,
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
 
         assert_eq!(expected_cfg, main_cfg);
@@ -519,6 +669,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: None,
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
         let missing_options = CfgFile {
             max_width: Some(20),
@@ -531,6 +685,10 @@ mod test {
             upstream_separator: Some("sep".into()),
             case: Some(Case::Ignore),
             features: Some("feature".into()),
+            wrap_mode: Some(WrapMode::Greedy),
+            max_link_text_width: Some(30),
+            template_delimiters: Some("hugo".into()),
+            max_blank_lines: Some(2),
         };
         let other_options = CfgFile {
             max_width: Some(10),
@@ -543,6 +701,10 @@ mod test {
             upstream_separator: Some("let's not split up".into()),
             case: Some(Case::Keep),
             features: Some("everything".into()),
+            wrap_mode: Some(WrapMode::Sembr),
+            max_link_text_width: Some(40),
+            template_delimiters: Some("jinja".into()),
+            max_blank_lines: Some(3),
         };
 
         let fully_defined = main_cfg.merge_with(&missing_options);
@@ -561,6 +723,10 @@ mod test {
             upstream_separator: Some("sep".into()),
             case: Some(Case::Ignore),
             features: Some("feature".into()),
+            wrap_mode: Some(WrapMode::Greedy),
+            max_link_text_width: Some(30),
+            template_delimiters: Some("hugo".into()),
+            max_blank_lines: Some(2),
         };
 
         assert_eq!(expected_cfg, main_cfg);
@@ -579,6 +745,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: None,
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
         let other_cfg = CfgFile {
             max_width: None,
@@ -591,6 +761,10 @@ mod test {
             upstream_separator: None,
             case: None,
             features: Some("feature".into()),
+            wrap_mode: None,
+            max_link_text_width: None,
+            template_delimiters: None,
+            max_blank_lines: None,
         };
         let default_cfg = CfgFile::default();
 
@@ -614,6 +788,10 @@ mod test {
             upstream_separator: "".into(),
             case: Case::Ignore,
             features: "feature".into(),
+            wrap_mode: WrapMode::Greedy,
+            max_link_text_width: 0,
+            template_delimiters: "".into(),
+            max_blank_lines: 1,
         };
 
         assert_eq!(expected_cfg, merged);
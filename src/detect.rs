@@ -162,6 +162,36 @@ impl BreakDetector {
     }
 }
 
+/// Checks whether a line starting with "word" could be parsed as the start of a block-level
+/// element, e.g. a list item, a heading, a block quote, a setext heading underline, a thematic
+/// break, a table row, a code fence, or a link definition, instead of being a continuation of the
+/// text before it.
+pub fn starts_block_element(word: &str) -> bool {
+    let is_ordered_list_marker = || {
+        word.strip_suffix(['.', ')']).is_some_and(|num| {
+            !num.is_empty() && num.len() <= 9 && num.chars().all(|ch| ch.is_ascii_digit())
+        })
+    };
+    let is_heading_marker = || word.len() <= 6 && word.chars().all(|ch| ch == '#');
+    let is_setext_underline =
+        || word.chars().all(|ch| ch == '=') || word.chars().all(|ch| ch == '-');
+    let is_thematic_break = || {
+        word.len() >= 3 && (word.chars().all(|ch| ch == '*') || word.chars().all(|ch| ch == '_'))
+    };
+    let is_link_def = || word.starts_with('[') && word.ends_with("]:");
+
+    !word.is_empty()
+        && (["-", "+", "*"].contains(&word)
+            || word.starts_with(['>', '|'])
+            || word.starts_with("```")
+            || word.starts_with("~~~")
+            || is_ordered_list_marker()
+            || is_heading_marker()
+            || is_setext_underline()
+            || is_thematic_break()
+            || is_link_def())
+}
+
 // Some helper functions that make it easier to work with Option<&char> follow.
 
 fn is_whitespace(ch: Option<&char>, detector: &WhitespaceDetector) -> bool {
@@ -177,6 +207,18 @@ mod test {
         keep_linebreaks: false,
     };
 
+    #[test]
+    fn detecting_block_elements() {
+        let block_elements = "- + * 1. 2) 123456789. # ###### > >quote | |cell = === --- *** ***** ___ ``` ```rust ~~~ [x]: [some-link]:";
+        for word in block_elements.split_whitespace() {
+            assert!(starts_block_element(word), "{}", word);
+        }
+        let other_words = "word *emphasis* ** 1234567890. 1.5 a. ####### #hashtag -word =word __ _ __init__ `` [x] [x]:y e.g.";
+        for word in other_words.split_whitespace().chain([""]) {
+            assert!(!starts_block_element(word), "{}", word);
+        }
+    }
+
     #[test]
     fn case_insensitive_match() {
         let detector = BreakDetector::new("ipsum sit adipiscing", "", false, "", CFG_FOR_TESTS);
@@ -26,6 +26,14 @@ pub struct FeatureCfg {
     pub format_block_quotes: bool,
     pub collate_link_defs: bool,
     pub outsource_inline_links: bool,
+    pub atomic_code_spans: bool,
+    pub format_directive_fences: bool,
+    pub quarto: bool,
+    pub normalize_lists: bool,
+    pub align_tables: bool,
+    pub normalize_headings: bool,
+    pub normalize_blank_lines: bool,
+    pub prune_link_defs: bool,
     pub break_cfg: BreakCfg,
     pub parse_cfg: ParseCfg,
 }
@@ -37,10 +45,15 @@ impl Default for FeatureCfg {
             format_block_quotes: false,
             collate_link_defs: false,
             outsource_inline_links: false,
-            parse_cfg: ParseCfg {
-                keep_linebreaks: false,
-                keep_colon_fences: false,
-            },
+            atomic_code_spans: false,
+            format_directive_fences: false,
+            quarto: false,
+            normalize_lists: false,
+            align_tables: false,
+            normalize_headings: false,
+            normalize_blank_lines: false,
+            prune_link_defs: false,
+            parse_cfg: ParseCfg::default(),
             break_cfg: BreakCfg {
                 keep_linebreaks: false,
             },
@@ -67,6 +80,13 @@ impl std::str::FromStr for FeatureCfg {
                 "format-block-quotes" => cfg.format_block_quotes = true,
                 "collate-link-defs" => cfg.collate_link_defs = true,
                 "outsource-inline-links" => cfg.outsource_inline_links = true,
+                "atomic-code-spans" => cfg.atomic_code_spans = true,
+                "format-directive-fences" => cfg.format_directive_fences = true,
+                "normalize-lists" => cfg.normalize_lists = true,
+                "align-tables" => cfg.align_tables = true,
+                "normalize-headings" => cfg.normalize_headings = true,
+                "normalize-blank-lines" => cfg.normalize_blank_lines = true,
+                "prune-link-defs" => cfg.prune_link_defs = true,
                 "keep-linebreaks" => {
                     cfg.parse_cfg.keep_linebreaks = true;
                     cfg.break_cfg.keep_linebreaks = true;
@@ -74,6 +94,35 @@ impl std::str::FromStr for FeatureCfg {
                 "keep-colon-fences" => {
                     cfg.parse_cfg.keep_colon_fences = true;
                 }
+                "format-admonitions" => {
+                    cfg.parse_cfg.format_admonitions = true;
+                }
+                "mdx" => {
+                    cfg.parse_cfg.mdx = true;
+                }
+                "wiki-links" => {
+                    cfg.parse_cfg.wiki_links = true;
+                }
+                // Fenced divs are handled like colon fences.
+                "pandoc" => {
+                    cfg.parse_cfg.pandoc = true;
+                    cfg.parse_cfg.keep_colon_fences = true;
+                }
+                "tasklists" => {
+                    cfg.parse_cfg.tasklists = true;
+                }
+                "heading-attributes" => {
+                    cfg.parse_cfg.heading_attributes = true;
+                }
+                "strikethrough" => {
+                    cfg.parse_cfg.strikethrough = true;
+                }
+                // Quarto and R Markdown build on Pandoc markdown.
+                "quarto" => {
+                    cfg.quarto = true;
+                    cfg.parse_cfg.pandoc = true;
+                    cfg.parse_cfg.keep_colon_fences = true;
+                }
                 // Do not accept any other entry.
                 _ => errors.push(feature),
             }
@@ -102,9 +151,24 @@ mod test {
             format_block_quotes: !default.format_block_quotes,
             collate_link_defs: !default.collate_link_defs,
             outsource_inline_links: !default.outsource_inline_links,
+            atomic_code_spans: !default.atomic_code_spans,
+            format_directive_fences: !default.format_directive_fences,
+            quarto: !default.quarto,
+            normalize_lists: !default.normalize_lists,
+            align_tables: !default.align_tables,
+            normalize_headings: !default.normalize_headings,
+            normalize_blank_lines: !default.normalize_blank_lines,
+            prune_link_defs: !default.prune_link_defs,
             parse_cfg: ParseCfg {
                 keep_linebreaks: !default.parse_cfg.keep_linebreaks,
                 keep_colon_fences: !default.parse_cfg.keep_colon_fences,
+                format_admonitions: !default.parse_cfg.format_admonitions,
+                mdx: !default.parse_cfg.mdx,
+                wiki_links: !default.parse_cfg.wiki_links,
+                pandoc: !default.parse_cfg.pandoc,
+                tasklists: !default.parse_cfg.tasklists,
+                heading_attributes: !default.parse_cfg.heading_attributes,
+                strikethrough: !default.parse_cfg.strikethrough,
             },
             break_cfg: BreakCfg {
                 keep_linebreaks: !default.break_cfg.keep_linebreaks,
@@ -112,7 +176,7 @@ mod test {
         };
 
         let parsed =
-            "keep-spaces-in-links , keep-linebreaks ,format-block-quotes, collate-link-defs,outsource-inline-links, keep-colon-fences"
+            "keep-spaces-in-links , keep-linebreaks ,format-block-quotes, collate-link-defs,outsource-inline-links, keep-colon-fences, atomic-code-spans, format-directive-fences, format-admonitions, mdx, wiki-links, pandoc, quarto, tasklists, heading-attributes, strikethrough, normalize-lists, align-tables, normalize-headings, normalize-blank-lines, prune-link-defs"
                 .parse::<FeatureCfg>()?;
 
         assert_eq!(parsed, swapped);
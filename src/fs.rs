@@ -21,7 +21,10 @@ use std::path::{Path, PathBuf};
 use anyhow::{Context, Error, Result};
 use ignore::Walk;
 
-pub fn find_files_with_extension(paths: &[PathBuf], extension: &str) -> Result<HashSet<PathBuf>> {
+pub fn find_files_with_extension(
+    paths: &[PathBuf],
+    extensions: &[String],
+) -> Result<HashSet<PathBuf>> {
     let mut errors = vec![];
 
     let found = paths
@@ -59,7 +62,10 @@ pub fn find_files_with_extension(paths: &[PathBuf], extension: &str) -> Result<H
                             }
                         })
                         // Only keep actual markdown files and symlinks to them.
-                        .filter(|el| el.is_file() && el.to_string_lossy().ends_with(extension))
+                        .filter(|el| {
+                            let path = el.to_string_lossy();
+                            el.is_file() && extensions.iter().any(|ext| path.ends_with(ext))
+                        })
                         .map(strip_cwd_if_possible)
                         .inspect(|el| {
                             log::debug!("discovered file on disk: {}", el.to_string_lossy());
@@ -76,9 +82,9 @@ pub fn find_files_with_extension(paths: &[PathBuf], extension: &str) -> Result<H
 
     if errors.is_empty() {
         log::debug!(
-            "discovered {} files with extension {}",
+            "discovered {} files with extensions {}",
             found.len(),
-            extension
+            extensions.join(" ")
         );
         Ok(found)
     } else {
@@ -189,7 +195,8 @@ mod test {
     // Actual tests follow.
     #[test]
     fn listing_non_existent_fails() {
-        let is_err = find_files_with_extension(&["i do not exist".into()], ".md").is_err();
+        let is_err =
+            find_files_with_extension(&["i do not exist".into()], &[".md".into()]).is_err();
         assert!(is_err);
     }
 
@@ -247,12 +254,16 @@ mod test {
         tmp.new_file_in_dir("other_dir/f_3.md".into())?;
         tmp.new_file_in_dir("other_dir/no_md_1.ext".into())?;
 
-        let ext_found = find_files_with_extension(&[tmp.0.path().into()], ".ext")?;
+        let ext_found = find_files_with_extension(&[tmp.0.path().into()], &[".ext".into()])?;
         assert_eq!(ext_found.len(), 4);
 
-        let found = find_files_with_extension(&[tmp.0.path().into()], ".md")?;
+        let found = find_files_with_extension(&[tmp.0.path().into()], &[".md".into()])?;
         assert_eq!(found.len(), 3);
 
+        let all_found =
+            find_files_with_extension(&[tmp.0.path().into()], &[".md".into(), ".ext".into()])?;
+        assert_eq!(all_found.len(), 7);
+
         Ok(())
     }
 
@@ -276,7 +287,7 @@ mod test {
         tmp.new_file_in_dir_with_content("dir/.ignore".into(), "file.md\n")?;
         tmp.new_file_in_dir_with_content("other_dir/.ignore".into(), "f*.md\n")?;
 
-        let found = find_files_with_extension(&[tmp.0.path().into()], ".md")?
+        let found = find_files_with_extension(&[tmp.0.path().into()], &[".md".into()])?
             .into_iter()
             .map(|el| tmp.strip(el))
             .map(|el| el.to_string_lossy().to_string())
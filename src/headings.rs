@@ -0,0 +1,245 @@
+/* An opinionated line wrapper for markdown files.
+Copyright (C) 2023  Torsten Long
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+use crate::ignore;
+use crate::parse::CharRange;
+use crate::trace_log;
+
+/// Normalise all headings in the document. Setext headings are converted to ATX headings, which
+/// means that multi-line setext headings are joined into a single line. Optional closing sequences
+/// of ATX headings are removed. Whitespace in heading texts is collapsed, except inside code spans.
+/// Setext headings in block quotes are kept as they are since their lines are prefixed. With
+/// heading attributes, a trailing attribute block like `{#id}` follows the text of a heading.
+/// Headings in ignored sections are kept as they are.
+pub fn normalize_headings(text: String, heading_attributes: bool) -> String {
+    let events = Parser::new(&text).into_offset_iter().collect::<Vec<_>>();
+    let code_ranges = events
+        .iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(..) => Some(range.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let ignored = ignore::find_ignored_ranges(&events, text.len());
+    let headings = events
+        .iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Heading { level, .. }) => Some((range.clone(), *level as usize)),
+            _ => None,
+        })
+        .filter(|(range, _level)| !ignored.iter().any(|el| el.contains(&range.start)))
+        .collect::<Vec<_>>();
+
+    // Replace headings from back to front. That way, the ranges of the remaining ones stay valid.
+    let mut result = text.clone();
+    for (range, level) in headings.into_iter().rev() {
+        let Some(start) = text[range.clone()]
+            .find(|ch| ch != ' ')
+            .map(|el| range.start + el)
+        else {
+            continue;
+        };
+        let end = start + text[start..range.end].trim_end_matches(['\n', '\r']).len();
+
+        // Setext headings span at least two lines while ATX headings are single lines. The text of
+        // a setext heading might start with a hash, too.
+        let is_setext = text[start..end].contains('\n');
+        let content = if is_setext {
+            setext_content(&text[start..end]).map(|len| start..start + len)
+        } else {
+            atx_content(&text[start..end]).map(|(offset, len)| start + offset..start + offset + len)
+        };
+
+        if let Some(content) = content {
+            let mut content = collapse_whitespace(&text, content, &code_ranges);
+            if is_setext {
+                escape_closing_sequence(&mut content, heading_attributes);
+            }
+            let heading = if content.is_empty() {
+                "#".repeat(level)
+            } else {
+                format!("{} {}", "#".repeat(level), content)
+            };
+            trace_log!("normalising heading: {}", heading);
+            result.replace_range(start..end, &heading);
+        }
+    }
+    result
+}
+
+/// Determine the offset and length of the text of an ATX heading, e.g. "Title" for "## Title ##".
+fn atx_content(heading: &str) -> Option<(usize, usize)> {
+    let after_opening = heading.trim_start_matches('#');
+    let offset = heading.len() - after_opening.trim_start().len();
+    let mut content = after_opening.trim();
+    // A closing sequence of hashes has to be preceded by whitespace unless it is all there is.
+    let without_closing = content.trim_end_matches('#');
+    if without_closing.is_empty() || without_closing.ends_with([' ', '\t']) {
+        content = without_closing.trim_end();
+    }
+    Some((offset, content.len()))
+}
+
+/// Determine the length of the text of a setext heading, i.e. everything before the underline.
+fn setext_content(heading: &str) -> Option<usize> {
+    let underline_start = heading.rfind('\n')?;
+    let content = &heading[..underline_start];
+    let is_prefixed = heading
+        .lines()
+        .skip(1)
+        .any(|line| line.trim_start().starts_with('>'));
+    (!is_prefixed).then_some(content.trim_end().len())
+}
+
+/// Escape the last hash of the text of a setext heading if it would otherwise be considered the
+/// closing sequence of an ATX heading, e.g. for "Title #". With heading attributes, a closing
+/// sequence may also precede an attribute block, e.g. for "Title # {#id}".
+fn escape_closing_sequence(content: &mut String, heading_attributes: bool) {
+    let end = if heading_attributes && content.ends_with('}') {
+        content
+            .rfind('{')
+            .map_or(content.len(), |el| content[..el].trim_end().len())
+    } else {
+        content.len()
+    };
+    let without_hashes = content[..end].trim_end_matches('#');
+    if without_hashes.len() < end
+        && (without_hashes.is_empty() || without_hashes.ends_with([' ', '\t']))
+    {
+        content.insert(end - 1, '\\');
+    }
+}
+
+/// Replace every sequence of whitespace by a single space, except inside code spans. There, only
+/// line breaks and the indent following them are replaced.
+fn collapse_whitespace(text: &str, content: CharRange, code_ranges: &[CharRange]) -> String {
+    let mut result = String::new();
+    let mut last_was_whitespace = false;
+    let mut after_linebreak = false;
+    let code_ranges = code_ranges
+        .iter()
+        .filter(|el| el.start < content.end && content.start < el.end)
+        .collect::<Vec<_>>();
+
+    for (idx, ch) in text[content.clone()].char_indices() {
+        let is_code = code_ranges
+            .iter()
+            .any(|el| el.contains(&(content.start + idx)));
+        let is_whitespace = ch.is_ascii_whitespace();
+
+        if is_code && !(is_whitespace && after_linebreak) {
+            if ch == '\n' {
+                result.push(' ');
+            } else {
+                result.push(ch);
+            }
+        } else if is_code {
+            // Skip the indent of a continuation line inside a code span.
+        } else if is_whitespace {
+            if !last_was_whitespace {
+                result.push(' ');
+            }
+        } else {
+            result.push(ch);
+        }
+        last_was_whitespace = is_whitespace;
+        after_linebreak = ch == '\n' || (after_linebreak && is_whitespace);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn converting_setext_headings_starting_or_ending_with_hashes() {
+        let text =
+            "#5 is the issue\n===\n\nTitle with trailing #\n---\n\nTitle ##\n---\n\nC#\n---\n";
+        let expected =
+            "# #5 is the issue\n\n## Title with trailing \\#\n\n## Title #\\#\n\n## C#\n";
+        assert_eq!(expected, normalize_headings(text.to_string(), false));
+    }
+
+    #[test]
+    fn converting_setext_headings_with_attributes() {
+        let text = "Title # {#id}\n---\n\nOther {.class}\n---\n";
+        let expected = "## Title # {#id}\n\n## Other {.class}\n";
+        assert_eq!(expected, normalize_headings(text.to_string(), false));
+        let expected = "## Title \\# {#id}\n\n## Other {.class}\n";
+        assert_eq!(expected, normalize_headings(text.to_string(), true));
+    }
+
+    #[test]
+    fn normalising_headings() {
+        let text = "\
+Some  title
+   spanning lines
+===
+
+Sub title
+---------
+
+##   Closed   heading ##
+### C#
+#### Escaped \\#
+##### `code   span`  text #####
+#
+
+- List item
+
+  Item heading
+  ---
+
+> Quoted
+> ---
+
+> ## Quoted ATX ##
+";
+        let expected = "\
+# Some title spanning lines
+
+## Sub title
+
+## Closed heading
+### C#
+#### Escaped \\#
+##### `code   span` text
+#
+
+- List item
+
+  ## Item heading
+
+> Quoted
+> ---
+
+> ## Quoted ATX
+";
+        assert_eq!(expected, normalize_headings(text.to_string(), false));
+    }
+
+    #[test]
+    fn keeping_headings_in_ignored_sections() {
+        let text = "<!-- mdslw-ignore-start -->\nTitle\n=====\n\n##  Sub  ##\n<!-- mdslw-ignore-end -->\n\nOther\n---\n";
+        let expected = "<!-- mdslw-ignore-start -->\nTitle\n=====\n\n##  Sub  ##\n<!-- mdslw-ignore-end -->\n\n## Other\n";
+        assert_eq!(expected, normalize_headings(text.to_string(), false));
+    }
+}
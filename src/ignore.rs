@@ -15,6 +15,10 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use pulldown_cmark::Event;
+
+use crate::parse::CharRange;
+
 const IGNORE_START: &str = "mdslw-ignore-start";
 const IGNORE_END: &str = "mdslw-ignore-end";
 
@@ -53,3 +57,28 @@ impl IgnoreByHtmlComment {
         self.ignore
     }
 }
+
+/// Find the ranges of all sections between two ignore directives. A section that is never ended
+/// extends to the end of the document, whose length is "text_len".
+pub fn find_ignored_ranges(events: &[(Event, CharRange)], text_len: usize) -> Vec<CharRange> {
+    let mut ignore = IgnoreByHtmlComment::new();
+    let mut ignore_start = 0;
+    let mut ranges = vec![];
+
+    for (event, range) in events {
+        if let Event::Html(html) = event {
+            let was_ignored = ignore.should_be_ignored();
+            ignore.process_html(html);
+            match (was_ignored, ignore.should_be_ignored()) {
+                (false, true) => ignore_start = range.start,
+                (true, false) => ranges.push(ignore_start..range.end),
+                _ => {}
+            }
+        }
+    }
+    if ignore.should_be_ignored() {
+        ranges.push(ignore_start..text_len);
+    }
+
+    ranges
+}
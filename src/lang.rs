@@ -15,10 +15,106 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::path::Path;
+
 use anyhow::{Error, Result};
 use include_dir::{Dir, include_dir};
 
-static LANG_FILES_DIR: Dir<'_> = include_dir!("$MDSLW_LANG_DIR");
+use crate::parse::get_scalar_value_for_yaml_key;
+
+// The suppression lists for all languages are vendored in this directory and will be incorporated
+// into the executable. Use "make vendor-langs" to update the lists taken from unicode's CLDR.
+static LANG_FILES_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/src/lang");
+
+// The special language name that causes the language to be detected per document.
+const AUTO_LANG: &str = "auto";
+// Keys in a document's YAML frontmatter that can specify the document's language.
+const FRONTMATTER_LANG_KEYS: [&str; 2] = ["lang", "language"];
+// The author's choice list is not associated with any locale and, thus, never auto-detected.
+const AUTHORS_CHOICE_LANG: &str = "ac";
+
+/// Replace the special language "auto" in the space-separated list of languages by the language
+/// detected for the document at "path" with the given frontmatter. If no language can be detected,
+/// "auto" is removed from the list. The following sources are checked in order:
+///   - a "lang" or "language" key in the frontmatter, e.g. "lang: de"
+///   - a suffix of the file name before the extension, e.g. "README.de.md"
+///   - the name of the closest directory below "root" that is a language, e.g. "docs/fr/index.md"
+pub fn resolve_auto_lang(lang_names: &str, frontmatter: &str, path: &Path, root: &Path) -> String {
+    if !lang_names
+        .split_terminator(',')
+        .flat_map(|el| el.split_whitespace())
+        .any(|el| el == AUTO_LANG)
+    {
+        return lang_names.to_string();
+    }
+
+    let detected = detect_lang(frontmatter, path, root);
+    if let Some(lang) = &detected {
+        log::debug!(
+            "detected language '{}' for {}",
+            lang,
+            path.to_string_lossy()
+        );
+    } else {
+        log::debug!("cannot detect language for {}", path.to_string_lossy());
+    }
+
+    lang_names
+        .split_terminator(',')
+        .flat_map(|el| el.split_whitespace())
+        .filter_map(|el| {
+            if el == AUTO_LANG {
+                detected.as_deref()
+            } else {
+                Some(el)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn detect_lang(frontmatter: &str, path: &Path, root: &Path) -> Option<String> {
+    let from_frontmatter = || {
+        FRONTMATTER_LANG_KEYS
+            .iter()
+            .filter_map(|key| get_scalar_value_for_yaml_key(frontmatter, key))
+            .find_map(|value| to_supported_lang(&value))
+    };
+    let from_file_name = || {
+        let file_name = path.file_name()?.to_string_lossy();
+        let parts = file_name.split('.').collect::<Vec<_>>();
+        // We need at least a name, a language, and an extension.
+        if parts.len() >= 3 {
+            to_supported_lang(parts[parts.len() - 2])
+        } else {
+            None
+        }
+    };
+    // Directories above the root, e.g. "it" for integration tests, are unrelated to the document.
+    let from_dirs = || {
+        path.parent()?
+            .strip_prefix(root)
+            .ok()?
+            .components()
+            .rev()
+            .find_map(|el| to_supported_lang(&el.as_os_str().to_string_lossy()))
+    };
+
+    from_frontmatter()
+        .or_else(from_file_name)
+        .or_else(from_dirs)
+}
+
+/// Map a locale like "de", "pt-BR", or "en_US" to a supported language, if there is one.
+fn to_supported_lang(locale: &str) -> Option<String> {
+    let locale = locale.trim().replace('_', "-");
+    let primary = locale.split('-').next().unwrap_or(&locale);
+    [locale.as_str(), primary]
+        .into_iter()
+        .filter(|el| !el.is_empty() && el != &AUTHORS_CHOICE_LANG)
+        .find(|el| LANG_FILES_DIR.get_file(el).is_some())
+        .map(String::from)
+}
 
 pub fn keep_word_list(lang_names: &str) -> Result<String> {
     let mut errors = vec![];
@@ -72,12 +168,64 @@ mod test {
 
     #[test]
     fn some_langs_are_supported() -> Result<()> {
-        let langs = "de en es fr it";
+        let langs = "de en es fr it pt ru";
         let list = keep_word_list(langs)?;
         assert_ne!(list, String::new());
         Ok(())
     }
 
+    #[test]
+    fn all_vendored_langs_are_supported() -> Result<()> {
+        for file in LANG_FILES_DIR.files() {
+            let lang = file.path().to_string_lossy();
+            let list = keep_word_list(&lang)?;
+            assert!(list.ends_with('\n'), "{}", lang);
+            assert_ne!(list.trim(), "", "{}", lang);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn auto_lang_is_kept_when_not_requested() {
+        let path = Path::new("docs/fr/README.it.md");
+        let resolved = resolve_auto_lang("ac en", "lang: de", path, Path::new(""));
+        assert_eq!(resolved, "ac en");
+    }
+
+    #[test]
+    fn auto_lang_detection_order() {
+        for (frontmatter, path, expected) in [
+            ("lang: de", "docs/fr/README.it.md", "ac de"),
+            ("language: pt-BR", "docs/fr/README.it.md", "ac pt"),
+            ("lang: unknown", "docs/fr/README.it.md", "ac it"),
+            ("", "docs/fr/README.it.md", "ac it"),
+            ("", "docs/fr/README.md", "ac fr"),
+            ("", "es/docs/en_GB/sub/README.md", "ac en"),
+            ("", "docs/README.ac.md", "ac"),
+            ("", "README.md", "ac"),
+            ("", "STDIN", "ac"),
+        ] {
+            let resolved =
+                resolve_auto_lang("ac auto", frontmatter, Path::new(path), Path::new(""));
+            assert_eq!(resolved, expected, "{} {}", frontmatter, path);
+        }
+    }
+
+    #[test]
+    fn auto_lang_ignores_dirs_above_root() {
+        let root = Path::new("/home/user/it/repo");
+        for (path, expected) in [
+            ("/home/user/it/repo/README.md", "ac"),
+            ("/home/user/it/repo/docs/README.md", "ac"),
+            ("/home/user/it/repo/docs/fr/README.md", "ac fr"),
+            ("/home/user/it/repo/tests/README.de.md", "ac de"),
+            ("/home/user/de/README.md", "ac"),
+        ] {
+            let resolved = resolve_auto_lang("ac auto", "", Path::new(path), root);
+            assert_eq!(resolved, expected, "{}", path);
+        }
+    }
+
     #[test]
     fn unsupported_langs() {
         let langs = "unsupported";
@@ -17,7 +17,7 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::collections::HashSet;
 
-use crate::detect::{BreakDetector, WhitespaceDetector};
+use crate::detect::{BreakDetector, WhitespaceDetector, starts_block_element};
 
 pub fn insert_linebreaks_after_sentence_ends(text: &str, detector: &BreakDetector) -> String {
     let merged = normalise_linebreaks(text, &detector.whitespace);
@@ -101,7 +101,13 @@ fn find_sentence_ends(text: &str, detector: &BreakDetector) -> HashSet<Char> {
                         && count >= 2
                 )
             {
-                Some([Char::Skip(idx + 1), Char::Split(idx + 2)])
+                // Don't start the next sentence on a new line if that would turn it into a
+                // block-level element such as a list item or a heading.
+                if starts_block_element(&next_word(&as_chars[idx + 1..], &detector.whitespace)) {
+                    None
+                } else {
+                    Some([Char::Skip(idx + 1), Char::Split(idx + 2)])
+                }
             } else {
                 None
             }
@@ -110,6 +116,15 @@ fn find_sentence_ends(text: &str, detector: &BreakDetector) -> HashSet<Char> {
         .collect::<HashSet<_>>()
 }
 
+/// Retrieve the first word in the given characters, ignoring leading whitespace.
+fn next_word(chars: &[char], detector: &WhitespaceDetector) -> String {
+    chars
+        .iter()
+        .skip_while(|ch| detector.is_whitespace(ch))
+        .take_while(|ch| !detector.is_whitespace(ch))
+        .collect::<String>()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -139,6 +154,21 @@ mod test {
         assert_eq!(expected, ends);
     }
 
+    #[test]
+    fn not_splitting_before_block_elements() {
+        let text = "Use version 2. 2. Then it works. - Or not. # Really. Yes.";
+        let detector = BreakDetector::new("", "", false, ".", CFG_FOR_TESTS);
+
+        let broken = insert_linebreaks_after_sentence_ends(text, &detector);
+
+        let expected = "Use version 2. 2.\nThen it works. - Or not. # Really.\nYes.";
+        assert_eq!(expected, broken);
+
+        let text = "A break. *** Another one. ___ Done.";
+        let broken = insert_linebreaks_after_sentence_ends(text, &detector);
+        assert_eq!("A break. *** Another one. ___ Done.", broken);
+    }
+
     #[test]
     fn finding_sentence_ends_with_hard_breaks() {
         let text = "words that.  \nare. followed by.  \nperiods. period.";
@@ -0,0 +1,417 @@
+/* An opinionated line wrapper for markdown files.
+Copyright (C) 2023  Torsten Long
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use crate::ignore;
+use crate::parse::CharRange;
+use crate::trace_log;
+
+/// The maximum number of spaces between a list marker and the item's text. If there are more, the
+/// text is an indented code block and the item's content starts one space after the marker.
+const MAX_SPACES_AFTER_MARKER: usize = 4;
+/// The largest number of an ordered list item. Numbers with more than nine digits do not start
+/// list items.
+const MAX_NUMBER: u64 = 999_999_999;
+
+#[derive(Debug, PartialEq)]
+enum ListMarker {
+    Bullet(char),
+    Ordered(u64, char),
+}
+
+impl ListMarker {
+    /// Parse the list marker at the start of "text", e.g. "-" or "12.".
+    fn parse(text: &str) -> Option<(Self, usize)> {
+        let first = text.chars().next()?;
+        if ['-', '*', '+'].contains(&first) {
+            return Some((Self::Bullet(first), 1));
+        }
+        let digits = text.chars().take_while(|ch| ch.is_ascii_digit()).count();
+        let delimiter = text[digits..].chars().next()?;
+        if digits > 0 && ['.', ')'].contains(&delimiter) {
+            let num = text[..digits].parse::<u64>().ok()?;
+            Some((Self::Ordered(num, delimiter), digits + 1))
+        } else {
+            None
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Self::Bullet(ch) => ch.to_string(),
+            Self::Ordered(num, delimiter) => format!("{}{}", num, delimiter),
+        }
+    }
+}
+
+/// MarkerChange describes how the list marker of a single item shall be modified.
+#[derive(Debug)]
+struct MarkerChange {
+    // Byte range of the marker in the document.
+    marker: CharRange,
+    new_marker: String,
+    // The column at which the item's content starts, before the change.
+    content_col: usize,
+    // All lines up to this byte position belong to the item.
+    item_end: usize,
+}
+
+/// Normalise all lists in the document. Ordered lists are renumbered sequentially starting at the
+/// number of their first item unless their first two items have the same number, in which case all
+/// items get that number. Bullet lists use the same bullet marker on the same nesting level, namely
+/// the one that the first bullet list on that level uses. The content of each item is re-indented
+/// to match the width of its new marker. Lists directly following another list on the same level
+/// keep their bullet markers because changing them would join both lists. Ordered lists whose
+/// renumbered items would exceed the maximum number keep their numbers. Items in ignored sections
+/// are never changed.
+pub fn normalize_lists(text: String) -> String {
+    let events = Parser::new(&text).into_offset_iter().collect::<Vec<_>>();
+    let changes = find_marker_changes(&text, &events);
+    if changes.is_empty() {
+        return text;
+    }
+    apply_marker_changes(&text, changes)
+}
+
+/// The list that is currently being processed.
+struct ListState {
+    keep_markers: bool,
+    next_number: Option<u64>,
+    same_numbers: bool,
+}
+
+fn find_marker_changes(text: &str, events: &[(Event, CharRange)]) -> Vec<MarkerChange> {
+    let mut lists: Vec<ListState> = vec![];
+    let mut bullets_per_level = HashMap::<usize, char>::new();
+    let mut last_list_ends = HashMap::<usize, usize>::new();
+    let mut changes = vec![];
+    let ignored = ignore::find_ignored_ranges(events, text.len());
+
+    for (idx, (event, range)) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::List(start)) => {
+                let level = lists.len();
+                let keep_markers = last_list_ends.get(&level).is_some_and(|end| {
+                    *end <= range.start && text[*end..range.start].trim().is_empty()
+                });
+                let item_starts = direct_item_starts(&events[idx + 1..]);
+                let same_numbers = start.is_some() && {
+                    let numbers = item_starts
+                        .iter()
+                        .take(2)
+                        .filter_map(|pos| find_marker(text, *pos))
+                        .map(|(_range, marker)| marker)
+                        .collect::<Vec<_>>();
+                    numbers.len() == 2 && numbers[0] == numbers[1]
+                };
+                // Without a next number, all items keep their numbers.
+                let next_number = start
+                    .filter(|num| same_numbers || num + item_starts.len() as u64 <= MAX_NUMBER + 1);
+                lists.push(ListState {
+                    keep_markers,
+                    next_number,
+                    same_numbers,
+                });
+            }
+            Event::End(TagEnd::List(..)) => {
+                lists.pop();
+                last_list_ends.insert(lists.len(), range.end);
+            }
+            Event::Start(Tag::Item) => {
+                let level = lists.len().saturating_sub(1);
+                let Some(list) = lists.last_mut() else {
+                    continue;
+                };
+                let Some((marker_range, marker)) = find_marker(text, range.start) else {
+                    continue;
+                };
+                if ignored.iter().any(|el| el.contains(&marker_range.start)) {
+                    continue;
+                }
+                let new_marker = match marker {
+                    ListMarker::Bullet(ch) if list.keep_markers => ListMarker::Bullet(ch),
+                    ListMarker::Bullet(ch) => {
+                        ListMarker::Bullet(*bullets_per_level.entry(level).or_insert(ch))
+                    }
+                    ListMarker::Ordered(num, delimiter) => {
+                        let new_num = list.next_number.unwrap_or(num);
+                        if !list.same_numbers && list.next_number.is_some() {
+                            list.next_number = Some(new_num + 1);
+                        }
+                        ListMarker::Ordered(new_num, delimiter)
+                    }
+                }
+                .to_text();
+
+                if new_marker != text[marker_range.clone()]
+                    && let Some(content_col) = content_col(text, &marker_range)
+                {
+                    trace_log!(
+                        "changing list marker '{}' to '{}'",
+                        &text[marker_range.clone()],
+                        new_marker
+                    );
+                    changes.push(MarkerChange {
+                        marker: marker_range,
+                        new_marker,
+                        content_col,
+                        item_end: range.end,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    changes
+}
+
+/// Determine the start positions of the items of the list whose start event precedes "events".
+fn direct_item_starts(events: &[(Event, CharRange)]) -> Vec<usize> {
+    let mut depth: usize = 0;
+    let mut starts = vec![];
+    for (event, range) in events {
+        match event {
+            Event::Start(tag) => {
+                if depth == 0 && tag == &Tag::Item {
+                    starts.push(range.start);
+                }
+                depth += 1;
+            }
+            Event::End(..) if depth == 0 => break,
+            Event::End(..) => depth -= 1,
+            _ => {}
+        }
+    }
+    starts
+}
+
+/// Find the list marker of the item starting at "item_start". Items in other containers than lists,
+/// e.g. block quotes, are not supported because their content lines are prefixed.
+fn find_marker(text: &str, item_start: usize) -> Option<(CharRange, ListMarker)> {
+    let start = item_start + text[item_start..].find(|ch| ch != ' ')?;
+    let line_start = text[..start].rfind('\n').map_or(0, |el| el + 1);
+    let is_supported = text[line_start..start]
+        .chars()
+        .all(|ch| ch == ' ' || ch.is_ascii_digit() || ['-', '*', '+', '.', ')'].contains(&ch));
+
+    let (marker, len) = ListMarker::parse(&text[start..])?;
+    is_supported.then_some((
+        CharRange {
+            start,
+            end: start + len,
+        },
+        marker,
+    ))
+}
+
+/// Determine the column at which an item's content starts. Tabs are not supported.
+fn content_col(text: &str, marker: &CharRange) -> Option<usize> {
+    let line_start = text[..marker.start].rfind('\n').map_or(0, |el| el + 1);
+    let line_end = text[marker.end..]
+        .find('\n')
+        .map_or(text.len(), |el| marker.end + el);
+    let after_marker = &text[marker.end..line_end];
+    if after_marker.contains('\t') {
+        return None;
+    }
+    let spaces = after_marker.len() - after_marker.trim_start_matches(' ').len();
+    let marker_end_col = marker.end - line_start;
+    if spaces == after_marker.len() || spaces > MAX_SPACES_AFTER_MARKER {
+        Some(marker_end_col + 1)
+    } else {
+        Some(marker_end_col + spaces)
+    }
+}
+
+/// Replace list markers and shift the content lines of the affected items. Lines of an item that
+/// are less indented than its content are lazy continuation lines, which are not shifted.
+fn apply_marker_changes(text: &str, changes: Vec<MarkerChange>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut line_start = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_end = line_start + line.len();
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let is_blank = line.trim().is_empty();
+
+        let shift = changes
+            .iter()
+            .filter(|el| el.marker.end <= line_start && line_start < el.item_end)
+            .filter(|el| !is_blank && indent >= el.content_col)
+            .map(|el| el.new_marker.len() as isize - el.marker.len() as isize)
+            .sum::<isize>();
+
+        // Replace markers from right to left to keep byte positions valid.
+        let mut new_line = line.to_string();
+        for change in changes
+            .iter()
+            .rev()
+            .filter(|el| line_start <= el.marker.start && el.marker.end <= line_end)
+        {
+            let marker = change.marker.start - line_start..change.marker.end - line_start;
+            new_line.replace_range(marker, &change.new_marker);
+        }
+
+        if shift > 0 {
+            result.push_str(&" ".repeat(shift as usize));
+            result.push_str(&new_line);
+        } else {
+            result.push_str(&new_line[shift.unsigned_abs()..]);
+        }
+        line_start = line_end;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn renumbering_ordered_lists() {
+        let text = "\
+8. eight
+9. nine
+   continued
+5. ten
+   ```
+   code
+   ```
+
+text
+
+1) one
+1) one
+3) one
+";
+        let expected = "\
+8. eight
+9. nine
+   continued
+10. ten
+    ```
+    code
+    ```
+
+text
+
+1) one
+1) one
+1) one
+";
+        assert_eq!(expected, normalize_lists(text.to_string()));
+    }
+
+    #[test]
+    fn unifying_bullets_per_level() {
+        let text = "\
+* a
+  + b
+  + c
+
+text
+
+- d
+  * e
+
+    more e
+- f
++ separate list
+";
+        let expected = "\
+* a
+  + b
+  + c
+
+text
+
+* d
+  + e
+
+    more e
+* f
++ separate list
+";
+        assert_eq!(expected, normalize_lists(text.to_string()));
+    }
+
+    #[test]
+    fn shifting_nested_content() {
+        let text = "\
+9. a
+7. b
+   - c
+
+     more c
+";
+        let expected = "\
+9. a
+10. b
+    - c
+
+      more c
+";
+        assert_eq!(expected, normalize_lists(text.to_string()));
+
+        let text = "\
+9. a
+9. b
+
+   9. c
+   1. d
+      text
+";
+        let expected = "\
+9. a
+9. b
+
+   9. c
+   10. d
+       text
+";
+        assert_eq!(expected, normalize_lists(text.to_string()));
+    }
+
+    #[test]
+    fn keeping_lists_in_block_quotes() {
+        let text = "> 1. a\n> 3. b\n";
+        assert_eq!(text, normalize_lists(text.to_string()));
+    }
+
+    #[test]
+    fn keeping_numbers_that_would_exceed_the_maximum() {
+        let text = "999999998. a\n7. b\n8. c\n";
+        assert_eq!(text, normalize_lists(text.to_string()));
+        let text = "999999997. a\n7. b\n8. c\n";
+        let expected = "999999997. a\n999999998. b\n999999999. c\n";
+        assert_eq!(expected, normalize_lists(text.to_string()));
+    }
+
+    #[test]
+    fn keeping_lists_in_ignored_sections() {
+        let text =
+            "<!-- mdslw-ignore-start -->\n3. a\n9. b\n<!-- mdslw-ignore-end -->\n\n3. c\n9. d\n";
+        let expected =
+            "<!-- mdslw-ignore-start -->\n3. a\n9. b\n<!-- mdslw-ignore-end -->\n\n3. c\n4. d\n";
+        assert_eq!(expected, normalize_lists(text.to_string()));
+    }
+}
@@ -16,6 +16,7 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 // Imports.
+mod blanks;
 mod call;
 mod cfg;
 mod detect;
@@ -23,14 +24,21 @@ mod diff;
 mod features;
 mod frontmatter;
 mod fs;
+mod headings;
 mod ignore;
 mod indent;
 mod lang;
 mod linebreak;
+mod lists;
 mod logging;
+mod mdx;
+mod pandoc;
 mod parse;
 mod ranges;
 mod replace;
+mod suggest;
+mod tables;
+mod template;
 mod wrap;
 
 use std::collections::{HashMap, HashSet};
@@ -43,6 +51,8 @@ use clap_complete::generate;
 use rayon::prelude::*;
 
 const CONFIG_FILE: &str = ".mdslw.toml";
+const MDX_EXTENSIONS: [&str; 1] = [".mdx"];
+const QUARTO_EXTENSIONS: [&str; 3] = [".qmd", ".Rmd", ".rmd"];
 
 fn generate_report(
     mode: &cfg::ReportMode,
@@ -73,6 +83,10 @@ struct Processor {
     feature_cfg: features::FeatureCfg,
     detector: detect::BreakDetector,
     max_width: Option<usize>,
+    wrap_mode: cfg::WrapMode,
+    max_link_text_width: Option<usize>,
+    template_delimiters: template::TemplateDelimiters,
+    max_blank_lines: usize,
 }
 
 impl Processor {
@@ -84,7 +98,7 @@ impl Processor {
             text
         } else {
             log::debug!("replacing spaces in links by non-breaking spaces");
-            replace::replace_spaces_in_links_by_nbsp(text)
+            replace::replace_spaces_in_links_by_nbsp(text, self.max_link_text_width)
         };
         let text = if self.feature_cfg.outsource_inline_links {
             log::debug!("outsourcing inline links");
@@ -104,12 +118,48 @@ impl Processor {
             log::debug!("not collating links at the end of the document");
             text
         };
-        let parsed = parse::parse_markdown(&text, &self.feature_cfg.parse_cfg);
+        let text = if self.feature_cfg.normalize_lists {
+            log::debug!("normalising list markers");
+            lists::normalize_lists(text)
+        } else {
+            log::debug!("not normalising list markers");
+            text
+        };
+        let text = if self.feature_cfg.align_tables {
+            log::debug!("aligning tables");
+            tables::align_tables(text)
+        } else {
+            log::debug!("not aligning tables");
+            text
+        };
+        let text = if self.feature_cfg.normalize_headings {
+            log::debug!("normalising headings");
+            headings::normalize_headings(text, self.feature_cfg.parse_cfg.heading_attributes)
+        } else {
+            log::debug!("not normalising headings");
+            text
+        };
+        let (text, atomic_mask) =
+            replace::AtomicMask::mask(text, &self.feature_cfg, &self.template_delimiters);
+        let parsed = parse::parse_markdown(
+            &text,
+            &self.feature_cfg.parse_cfg,
+            &self.template_delimiters,
+        );
         let filled = ranges::fill_markdown_ranges(parsed, &text);
+        let (text, filled) = if self.feature_cfg.normalize_blank_lines {
+            log::debug!("normalising blank lines and trailing whitespace");
+            blanks::normalize_blank_lines(filled, &text, self.max_blank_lines)
+        } else {
+            log::debug!("not normalising blank lines and trailing whitespace");
+            (text, filled)
+        };
         let width = &self
             .max_width
             .map(|el| el.checked_sub(width_reduction).unwrap_or(el));
-        let formatted = wrap::add_linebreaks_and_wrap(filled, width, &self.detector, &text);
+        let formatted =
+            wrap::add_linebreaks_and_wrap(filled, width, &self.wrap_mode, &self.detector, &text);
+        let formatted = atomic_mask.unmask(formatted);
 
         // Keep newlines at the end of the file in tact. They disappear sometimes.
         let file_end = if !formatted.ends_with('\n') && ends_on_linebreak {
@@ -120,19 +170,45 @@ impl Processor {
         };
         let text = format!("{}{}", formatted, file_end);
 
-        // At last, process all block quotes.
-        if self.feature_cfg.format_block_quotes {
+        // At last, process all block quotes, directives, admonitions, and jsx elements.
+        let text = if self.feature_cfg.format_block_quotes {
             log::debug!("formatting text in block quotes");
             parse::BlockQuotes::new(&text)
                 .apply_to_matches_and_join(|t, indent| self.process(t, indent + width_reduction))
         } else {
             log::debug!("not formatting text in block quotes");
             text
+        };
+        let text = if self.feature_cfg.format_directive_fences {
+            log::debug!("formatting text in directives");
+            parse::DirectiveFences::new(&text)
+                .apply_to_matches_and_join(|t, indent| self.process(t, indent + width_reduction))
+        } else {
+            log::debug!("not formatting text in directives");
+            text
+        };
+        let text = if self.feature_cfg.parse_cfg.format_admonitions {
+            log::debug!("formatting text in admonitions");
+            parse::Admonitions::new(&text)
+                .apply_to_matches_and_join(|t, indent| self.process(t, indent + width_reduction))
+        } else {
+            log::debug!("not formatting text in admonitions");
+            text
+        };
+        if self.feature_cfg.parse_cfg.mdx {
+            log::debug!("formatting children of jsx elements");
+            parse::JsxChildren::new(&text)
+                .apply_to_matches_and_join(|t, indent| self.process(t, indent + width_reduction))
+        } else {
+            log::debug!("not formatting children of jsx elements");
+            text
         }
     }
 }
 
-fn process(document: String, file_dir: &Path, cfg: &cfg::PerFileCfg) -> Result<(String, String)> {
+fn build_features_and_detector(
+    cfg: &cfg::PerFileCfg,
+) -> Result<(features::FeatureCfg, detect::BreakDetector)> {
     // Prepare user-configured options. These could be outsourced if we didn't intend to allow
     // per-file configurations.
     let lang_keep_words = lang::keep_word_list(&cfg.lang).context("cannot load keep words")?;
@@ -147,6 +223,11 @@ fn process(document: String, file_dir: &Path, cfg: &cfg::PerFileCfg) -> Result<(
         &cfg.end_markers,
         &feature_cfg.break_cfg,
     );
+    Ok((feature_cfg, detector))
+}
+
+fn process(document: String, file_dir: &Path, cfg: &cfg::PerFileCfg) -> Result<(String, String)> {
+    let (feature_cfg, detector) = build_features_and_detector(cfg)?;
     let max_width = if cfg.max_width == 0 {
         log::debug!("not limiting line length");
         None
@@ -154,10 +235,28 @@ fn process(document: String, file_dir: &Path, cfg: &cfg::PerFileCfg) -> Result<(
         log::debug!("limiting line length to {} characters", cfg.max_width);
         Some(cfg.max_width)
     };
+    let max_link_text_width = if cfg.max_link_text_width == 0 {
+        log::debug!("keeping all link texts on a single line");
+        None
+    } else {
+        log::debug!(
+            "wrapping link texts longer than {} characters",
+            cfg.max_link_text_width
+        );
+        Some(cfg.max_link_text_width)
+    };
+    let template_delimiters = cfg
+        .template_delimiters
+        .parse::<template::TemplateDelimiters>()
+        .context("cannot parse template delimiters")?;
     let processor = Processor {
         feature_cfg,
         detector,
         max_width,
+        wrap_mode: cfg.wrap_mode,
+        max_link_text_width,
+        template_delimiters,
+        max_blank_lines: cfg.max_blank_lines,
     };
 
     // Actually process the text.
@@ -176,11 +275,21 @@ fn process(document: String, file_dir: &Path, cfg: &cfg::PerFileCfg) -> Result<(
         text
     };
 
-    let processed = format!("{}{}", frontmatter, processor.process(after_upstream, 0));
+    // Link definitions are pruned for the entire document at once because processing recurses
+    // into block quotes, whose link definitions might be referenced from outside of them.
+    let after_pruning = if processor.feature_cfg.prune_link_defs {
+        log::debug!("removing unused link definitions");
+        replace::prune_link_defs(after_upstream, &processor.detector.whitespace)
+    } else {
+        log::debug!("not removing unused link definitions");
+        after_upstream
+    };
+
+    let processed = format!("{}{}", frontmatter, processor.process(after_pruning, 0));
     Ok((processed, document))
 }
 
-fn process_stdin<F>(mode: &cfg::OpMode, build_cfg: F, file_path: &PathBuf) -> Result<bool>
+fn process_stdin<F>(mode: &cfg::OpMode, build_cfg: F, file_path: &PathBuf) -> Result<Outcome>
 where
     F: Fn(&str, &PathBuf) -> Result<cfg::PerFileCfg>,
 {
@@ -194,6 +303,8 @@ where
         .map(|el| el.to_path_buf())
         .unwrap_or(PathBuf::from("."));
     let (processed, text) = process(text, file_dir.as_path(), &config)?;
+    let references_defined = mode == &cfg::OpMode::Format
+        || report_undefined_references(&text, &file_path.to_string_lossy(), &config)?;
 
     // Decide what to output.
     match mode {
@@ -207,10 +318,17 @@ where
         }
     }
 
-    Ok(processed == text)
+    Ok(Outcome {
+        unchanged: processed == text,
+        references_defined,
+    })
 }
 
-fn process_file<F>(mode: &cfg::OpMode, path: &PathBuf, build_cfg: F) -> Result<(String, String)>
+fn process_file<F>(
+    mode: &cfg::OpMode,
+    path: &PathBuf,
+    build_cfg: F,
+) -> Result<(String, String, bool)>
 where
     F: Fn(&str, &PathBuf) -> Result<cfg::PerFileCfg>,
 {
@@ -220,6 +338,8 @@ where
     let (text, file_dir) = fs::get_file_content_and_dir(path)?;
     let config = build_cfg(&text, path).context("failed to build complete config")?;
     let (processed, text) = process(text, &file_dir, &config)?;
+    let references_defined =
+        mode == &cfg::OpMode::Format || report_undefined_references(&text, &report_path, &config)?;
 
     // Decide whether to overwrite existing files.
     match mode {
@@ -237,7 +357,37 @@ where
         }
     }
 
-    Ok((processed, text))
+    Ok((processed, text, references_defined))
+}
+
+/// Warn about references like `[text][label]` that lack a matching link definition if link
+/// definitions are pruned. Those are rendered as literal text, which is easy to miss. Line numbers
+/// refer to the entire document, including any frontmatter. Returns whether all references are
+/// defined.
+fn report_undefined_references(
+    document: &str,
+    report_path: &str,
+    cfg: &cfg::PerFileCfg,
+) -> Result<bool> {
+    let feature_cfg = cfg
+        .features
+        .parse::<features::FeatureCfg>()
+        .context("cannot parse selected features")?;
+    if !feature_cfg.prune_link_defs {
+        return Ok(true);
+    }
+    let frontmatter = frontmatter::extract_frontmatter(document);
+    let undefined = replace::find_undefined_references(&document[frontmatter.len()..]);
+    for (pos, reference) in undefined.iter() {
+        let line = document[..frontmatter.len() + pos].matches('\n').count() + 1;
+        log::warn!(
+            "{}:{}: undefined link reference: {}",
+            report_path,
+            line,
+            reference
+        );
+    }
+    Ok(undefined.is_empty())
 }
 
 fn read_config_file(path: &Path) -> Option<(PathBuf, cfg::CfgFile)> {
@@ -275,7 +425,101 @@ fn build_document_specific_config(
         )
     })?;
     let config_tuple = [(document_path.to_path_buf(), config_from_frontmatter)];
-    Ok(cfg::merge_configs(cli, config_tuple.iter().chain(configs)))
+    let mut config = cfg::merge_configs(cli, config_tuple.iter().chain(configs));
+    let absolute_path = document_path
+        .canonicalize()
+        .or_else(|_| std::path::absolute(document_path))
+        .unwrap_or_else(|_| document_path.to_path_buf());
+    config.lang = lang::resolve_auto_lang(
+        &config.lang,
+        &frontmatter::extract_frontmatter(document),
+        &absolute_path,
+        &lang_root_dir(&absolute_path),
+    );
+    Ok(config)
+}
+
+/// Determine the directory below which directory names may specify a document's language. That is
+/// the outermost directory containing a config file or, if there is none, the working directory.
+/// Directories above a repository, e.g. "it" or "de", are unrelated to its documents.
+fn lang_root_dir(document_path: &Path) -> PathBuf {
+    fs::UpwardsDirsIterator::new(document_path)
+        .filter(|el| el.join(CONFIG_FILE).is_file())
+        .last()
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default()
+}
+
+fn suggest_suppressions(cli: &cfg::CliArgs, paths: &[PathBuf]) -> Result<()> {
+    let mut candidates = suggest::SuppressionCandidates::new();
+    let mut scan = |document: String, document_path: &Path, config_dir: &Path| -> Result<()> {
+        let configs = fs::find_files_upwards(config_dir, CONFIG_FILE, &mut None)
+            .into_iter()
+            .filter_map(|el| read_config_file(&el))
+            .collect::<Vec<_>>();
+        let config = build_document_specific_config(&document, document_path, cli, &configs)
+            .context("failed to build complete config")?;
+        let (mut feature_cfg, detector) = build_features_and_detector(&config)?;
+        // Inline code is never prose, which is why code spans are always masked.
+        feature_cfg.atomic_code_spans = true;
+        let template_delimiters = config
+            .template_delimiters
+            .parse::<template::TemplateDelimiters>()
+            .context("cannot parse template delimiters")?;
+        let frontmatter = frontmatter::extract_frontmatter(&document);
+        candidates.add_document(
+            &document[frontmatter.len()..],
+            &detector,
+            &feature_cfg,
+            &template_delimiters,
+        );
+        Ok(())
+    };
+
+    if paths.is_empty() {
+        log::debug!("scanning content from stdin for suppressions");
+        let file_path = cli.stdin_filepath.clone().unwrap_or(PathBuf::from("STDIN"));
+        let cwd = PathBuf::from(".");
+        let file_dir = file_path.parent().unwrap_or(cwd.as_path());
+        scan(fs::read_stdin(), &file_path, file_dir)?;
+    } else {
+        let md_files = fs::find_files_with_extension(paths, &file_extensions(cli)?)
+            .context("failed to discover markdown files")?;
+        log::debug!(
+            "will scan {} markdown file(s) for suppressions",
+            md_files.len()
+        );
+        for path in md_files {
+            log::info!("scanning markdown file {}", path.to_string_lossy());
+            let (text, _file_dir) = fs::get_file_content_and_dir(&path)?;
+            scan(text, &path, &path)
+                .with_context(|| format!("failed to scan {}", path.to_string_lossy()))?;
+        }
+    }
+
+    print!("{}", candidates.to_config());
+    Ok(())
+}
+
+/// Determine the file extensions used to discover markdown files in directories. Features that
+/// support further file types add their extensions, but only if they are enabled on the command
+/// line or via environment variables because files are discovered before reading config files.
+fn file_extensions(cli: &cfg::CliArgs) -> Result<Vec<String>> {
+    let feature_cfg = cli
+        .features
+        .resolve(None)
+        .parse::<features::FeatureCfg>()
+        .context("cannot parse selected features")?;
+    let mut extensions = vec![cli.extension.clone()];
+    if feature_cfg.parse_cfg.mdx {
+        log::debug!("also discovering mdx files");
+        extensions.extend(MDX_EXTENSIONS.iter().map(|el| el.to_string()));
+    }
+    if feature_cfg.quarto {
+        log::debug!("also discovering quarto and r markdown files");
+        extensions.extend(QUARTO_EXTENSIONS.iter().map(|el| el.to_string()));
+    }
+    Ok(extensions)
 }
 
 fn print_config_file() -> Result<()> {
@@ -303,10 +547,15 @@ fn _main() -> Result<()> {
         log::info!("writing default config file to stdout");
         return print_config_file();
     }
+    // Suggestion of suppressions based on existing documents.
+    if let Some(cfg::Command::SuggestSuppressions { paths }) = &cli.command {
+        log::info!("suggesting suppressions");
+        return suggest_suppressions(&cli, paths);
+    }
 
     // All other actions could technically be specified on a per-file level.
     let cwd = PathBuf::from(".");
-    let unchanged = if cli.paths.is_empty() {
+    let outcome = if cli.paths.is_empty() {
         let file_path = cli.stdin_filepath.clone().unwrap_or(PathBuf::from("STDIN"));
         let file_dir = file_path.parent().unwrap_or(cwd.as_path());
         let configs = fs::find_files_upwards(file_dir, CONFIG_FILE, &mut None)
@@ -318,7 +567,7 @@ fn _main() -> Result<()> {
         };
         process_stdin(&cli.mode, build_document_config, &file_path)
     } else {
-        let md_files = fs::find_files_with_extension(&cli.paths, &cli.extension)
+        let md_files = fs::find_files_with_extension(&cli.paths, &file_extensions(&cli)?)
             .context("failed to discover markdown files")?;
         log::debug!("will process {} markdown file(s) from disk", md_files.len());
         let config_files = {
@@ -365,11 +614,14 @@ fn _main() -> Result<()> {
                     build_document_specific_config(document, file_path, &cli, &configs)
                 };
                 match process_file(&cli.mode, path, build_document_config) {
-                    Ok((processed, text)) => {
+                    Ok((processed, text, references_defined)) => {
                         if let Some(rep) = generate_report(&cli.report, &processed, &text, path) {
                             par_printer.println(&rep);
                         }
-                        Ok(processed == text)
+                        Ok(Outcome {
+                            unchanged: processed == text,
+                            references_defined,
+                        })
                     }
                     Err(err) => {
                         log::error!("failed to process {}: {:?}", path.to_string_lossy(), err);
@@ -378,36 +630,81 @@ fn _main() -> Result<()> {
                 }
             })
             .reduce(
-                || Ok(true),
+                || Ok(Outcome::default()),
                 |a, b| match (a, b) {
                     (Err(err), _) => Err(err),
                     (_, Err(err)) => Err(err),
-                    (Ok(f1), Ok(f2)) => Ok(f1 && f2),
+                    (Ok(o1), Ok(o2)) => Ok(o1.and(o2)),
                 },
             )
     };
 
     log::debug!("finished execution");
-    // Process exit code.
-    match unchanged {
-        Ok(true) => Ok(()),
-        Ok(false) => match cli.mode {
+    exit_status(&cli.mode, &cli.report, outcome)
+}
+
+/// Outcome describes the result of processing one or more documents.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Outcome {
+    // Whether no document was or would be changed.
+    unchanged: bool,
+    // Whether all link references have a matching definition. Only checked when pruning link
+    // definitions in the check and both modes.
+    references_defined: bool,
+}
+
+impl Default for Outcome {
+    fn default() -> Self {
+        Self {
+            unchanged: true,
+            references_defined: true,
+        }
+    }
+}
+
+impl Outcome {
+    fn and(self, other: Self) -> Self {
+        Self {
+            unchanged: self.unchanged && other.unchanged,
+            references_defined: self.references_defined && other.references_defined,
+        }
+    }
+}
+
+/// Determine the exit status of the program from the outcome of processing all documents.
+fn exit_status(
+    mode: &cfg::OpMode,
+    report: &cfg::ReportMode,
+    outcome: Result<Outcome>,
+) -> Result<()> {
+    match outcome {
+        Ok(Outcome {
+            unchanged: false, ..
+        }) => match mode {
             cfg::OpMode::Format => Ok(()),
             cfg::OpMode::Check => {
-                if cli.report == cfg::ReportMode::None {
+                if report == &cfg::ReportMode::None {
                     Err(Error::msg("at least one processed file would be changed"))
                 } else {
                     Err(Error::msg(""))
                 }
             }
             cfg::OpMode::Both => {
-                if cli.report == cfg::ReportMode::None {
+                if report == &cfg::ReportMode::None {
                     Err(Error::msg("at least one processed file changed"))
                 } else {
                     Err(Error::msg(""))
                 }
             }
         },
+        // Undefined references have been reported as warnings already.
+        Ok(Outcome {
+            references_defined: false,
+            ..
+        }) if mode != &cfg::OpMode::Format => Err(Error::msg(
+            "at least one processed file has undefined link references",
+        )),
+        Ok(_) => Ok(()),
         Err(err) => Err(err),
     }
 }
@@ -420,3 +717,225 @@ fn main() {
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Process a document end to end the way the CLI with the given arguments would.
+    fn format(text: &str, args: &[&str]) -> String {
+        let cli = cfg::CliArgs::parse_from(["mdslw"].iter().chain(args));
+        let cfg = cfg::merge_configs(&cli, &Vec::<(PathBuf, cfg::CfgFile)>::new());
+        let (processed, _document) =
+            process(text.to_string(), Path::new("."), &cfg).expect("processing document");
+        processed
+    }
+
+    #[test]
+    fn keeping_lines_with_only_templates_as_they_are() {
+        let text = "{{< tabs >}}\n{{< tab \"A\" >}}\nText. More.\n{{< /tab >}}\n{{< /tabs >}}\n";
+        let expected =
+            "{{< tabs >}}\n{{< tab \"A\" >}}\nText.\nMore.\n{{< /tab >}}\n{{< /tabs >}}\n";
+        assert_eq!(expected, format(text, &["--template-delimiters=hugo"]));
+    }
+
+    #[test]
+    fn formatting_children_of_jsx_elements() {
+        let text = "\
+import Tabs from \"@theme/Tabs\";
+
+<Tabs groupId=\"os\">
+<TabItem value=\"linux\" label=\"Linux\">
+Run this. Then that.
+</TabItem>
+<TabItem
+  value=\"mac\"
+  label=\"Mac\">
+  Use <Badge text=\"a b\" /> here. And {props.x} there.
+</TabItem>
+</Tabs>
+
+<div align=\"center\">
+  <img src=\"logo.png\" alt=\"The logo\" />
+</div>
+
+<pre>
+Keep this. As it is.
+</pre>
+";
+        let expected = "\
+import Tabs from \"@theme/Tabs\";
+
+<Tabs groupId=\"os\">
+<TabItem value=\"linux\" label=\"Linux\">
+Run this.
+Then that.
+</TabItem>
+<TabItem
+  value=\"mac\"
+  label=\"Mac\">
+  Use <Badge text=\"a b\" /> here.
+  And {props.x} there.
+</TabItem>
+</Tabs>
+
+<div align=\"center\">
+  <img src=\"logo.png\" alt=\"The logo\" />
+</div>
+
+<pre>
+Keep this. As it is.
+</pre>
+";
+        let formatted = format(text, &["--features=mdx"]);
+        assert_eq!(expected, formatted);
+        assert_eq!(formatted, format(&formatted, &["--features=mdx"]));
+    }
+
+    #[test]
+    fn keeping_line_blocks_before_fenced_divs() {
+        let text = "| Line one\n| Line two. Three\n\n:::\nBody. More.\n:::\n";
+        let expected = "| Line one\n| Line two. Three\n\n:::\nBody.\nMore.\n:::\n";
+        assert_eq!(expected, format(text, &["--features=pandoc"]));
+    }
+
+    #[test]
+    fn keeping_strikethrough_text_together() {
+        let text = "Some ~~old. Text~~ here.\n";
+        assert_eq!(text, format(text, &["--features=strikethrough"]));
+        assert_eq!("Some ~~old.\nText~~ here.\n", format(text, &[]));
+    }
+
+    #[test]
+    fn indenting_wrapped_tasks_relative_to_their_text() {
+        let text = "- [ ] Do the first thing now. Then do the second one.\n";
+        let expected =
+            "- [ ] Do the first\n      thing now.\n      Then do the\n      second one.\n";
+        assert_eq!(
+            expected,
+            format(text, &["--features=tasklists", "--max-width=20"])
+        );
+        let expected = "- [ ] Do the first\n  thing now.\n  Then do the second\n  one.\n";
+        assert_eq!(expected, format(text, &["--max-width=20"]));
+    }
+
+    #[test]
+    fn keeping_heading_attributes() {
+        let text = "# Heading {#id}\n\n## Some heading. With more words {#other .class}\n";
+        let args = ["--features=heading-attributes", "--max-width=10"];
+        assert_eq!(text, format(text, &args));
+        let text = "Title # {#id}\n---\n";
+        let args = ["--features=heading-attributes,normalize-headings"];
+        assert_eq!("## Title \\# {#id}\n", format(text, &args));
+    }
+
+    #[test]
+    fn keeping_headings_and_table_rows_with_links_and_emphasis_together() {
+        let text = "# Title *a long. Text here* end\n\n| *a long. Text here* | b |\n|---|---|\n";
+        assert_eq!(text, format(text, &["--max-width=10"]));
+        let text = "# Title [a long link. Text here](url) end\n";
+        let args = ["--max-width=10", "--features=keep-spaces-in-links"];
+        assert_eq!(text, format(text, &args));
+    }
+
+    #[test]
+    fn normalising_blank_lines_twice_changes_nothing() {
+        let text = "\
+# Serde &emsp; [![Build Status]][actions] [![Latest Version]][crates.io] [![serde msrv]][Rust 1.31]
+[Build Status]: https://img.shields.io/github/actions/workflow/status/serde-rs/serde/ci.yml
+[actions]: https://github.com/serde-rs/serde/actions?query=branch%3Amaster
+[Latest Version]: https://img.shields.io/crates/v/serde.svg
+[crates.io]: https://crates.io/crates/serde
+[serde msrv]: https://img.shields.io/crates/msrv/serde.svg
+[Rust 1.31]: https://blog.rust-lang.org/2018/12/06/Rust-1.31-and-rust-2018.html
+**Serde is a framework for *ser*ializing and *de*serializing Rust data structures efficiently and generically.**
+* [Serde (website)](https://serde.rs/). And more.
+```rust
+let x = 1;
+```
+";
+        let args = ["--features=normalize-blank-lines"];
+        let formatted = format(text, &args);
+        // Headings are never wrapped, but spaces in their link texts become non-breaking ones.
+        let heading = formatted.replace('\u{a0}', " ");
+        assert_eq!(text.lines().next(), heading.lines().next());
+        assert_eq!(formatted, format(&formatted, &args));
+    }
+
+    #[test]
+    fn failing_checks_for_undefined_references() {
+        let status = |args: &[&str], document: &str| {
+            let cli = cfg::CliArgs::parse_from(["mdslw"].iter().chain(args));
+            let cfg = cfg::merge_configs(&cli, &Vec::<(PathBuf, cfg::CfgFile)>::new());
+            let references_defined = report_undefined_references(document, "test.md", &cfg)
+                .expect("checking references");
+            let outcome = Outcome {
+                unchanged: true,
+                references_defined,
+            };
+            exit_status(&cli.mode, &cli.report, Ok(outcome))
+        };
+        let undefined = "See [text][missing].\n";
+        let defined = "See [text][label].\n\n[label]: https://a.com\n";
+
+        let args = ["--features=prune-link-defs", "--mode=check"];
+        assert!(status(&args, undefined).is_err());
+        assert!(status(&args, defined).is_ok());
+        let args = ["--features=prune-link-defs", "--mode=both"];
+        assert!(status(&args, undefined).is_err());
+        // Undefined references are only reported when pruning link definitions and checking.
+        assert!(status(&["--mode=check"], undefined).is_ok());
+        let outcome = Outcome {
+            unchanged: true,
+            references_defined: false,
+        };
+        assert!(exit_status(&cfg::OpMode::Format, &cfg::ReportMode::None, Ok(outcome)).is_ok());
+    }
+
+    #[test]
+    fn pruning_link_defs_for_the_entire_document() {
+        let text = "See [the link][a] here.\n\n> Quote text.\n>\n> [a]: https://a.com\n";
+        let args = ["--features=prune-link-defs,format-block-quotes,keep-spaces-in-links"];
+        assert_eq!(text, format(text, &args));
+
+        let text = "> See [the link][a] here.\n\n[a]: https://a.com\n[b]: https://b.com\n";
+        let expected = "> See [the link][a] here.\n\n[a]: https://a.com\n";
+        assert_eq!(expected, format(text, &args));
+    }
+
+    #[test]
+    fn pruning_link_defs_twice_changes_nothing() {
+        let text = "\
+# Crate [![Build Status]][actions] [![Latest Version]][crates.io]
+Links:
+-   [API documentation](https://docs.rs/a)
+
+[rand]: https://crates.io/crates/rand
+[^1]: D. J. Bernstein, [*ChaCha, a variant of Salsa20*](
+      https://cr.yp.to/chacha.html)
+
+    Indented [see here][indented].
+
+[Build Status]: https://a.com/badge.svg
+[actions]: https://b.com
+[Latest Version]: https://c.com/badge.svg
+[crates.io]: https://crates.io
+[indented]: https://d.com
+";
+        let args = ["--features=prune-link-defs,normalize-blank-lines"];
+        let formatted = format(text, &args);
+        assert!(!formatted.contains("[rand]:"));
+        // Spaces in link labels become non-breaking ones.
+        let defs = formatted.replace('\u{a0}', " ");
+        for label in [
+            "Build Status",
+            "actions",
+            "Latest Version",
+            "crates.io",
+            "indented",
+        ] {
+            assert!(defs.contains(&format!("[{}]: ", label)));
+        }
+        assert_eq!(formatted, format(&formatted, &args));
+    }
+}
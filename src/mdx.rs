@@ -0,0 +1,289 @@
+/* An opinionated line wrapper for markdown files.
+Copyright (C) 2023  Torsten Long
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use crate::ignore;
+use crate::parse::{self, CharRange};
+use crate::trace_log;
+
+// HTML blocks starting with these tags contain raw text, not markdown.
+const RAW_TEXT_TAGS: [&str; 4] = ["pre", "script", "style", "textarea"];
+
+/// Find JSX tags, e.g. `<Tabs defaultValue="a">` or `</Tabs>`, and JavaScript expressions, e.g.
+/// `{props.x}`, outside of code and HTML blocks. Tags and expressions may span several lines but
+/// never an empty line. The returned ranges are sorted and do not overlap.
+pub fn find_jsx(text: &str) -> Vec<CharRange> {
+    // Block-level tags are parsed as HTML blocks, which are kept verbatim anyway. Masking them
+    // would keep the parser from recognising those blocks.
+    let mut code_ranges = parse::find_code_ranges(text);
+    code_ranges.extend(Parser::new(text).into_offset_iter().filter_map(
+        |(event, range)| match event {
+            Event::Start(Tag::HtmlBlock) => Some(range),
+            _ => None,
+        },
+    ));
+    code_ranges.sort_by_key(|el| el.start);
+
+    let mut ranges = vec![];
+    let mut idx = 0;
+    // Index of the first code range that does not end before "idx".
+    let mut code_idx = 0;
+    while idx < text.len() {
+        while code_ranges.get(code_idx).is_some_and(|el| el.end <= idx) {
+            code_idx += 1;
+        }
+        if let Some(code) = code_ranges.get(code_idx).filter(|el| el.contains(&idx)) {
+            idx = code.end;
+            continue;
+        }
+
+        let rest = &text[idx..];
+        let end = if is_tag_start(rest) || rest.starts_with('{') {
+            find_end(rest).map(|el| idx + el)
+        } else {
+            None
+        };
+
+        if let Some(end) = end {
+            trace_log!("found jsx: {}", text[idx..end].replace('\n', "\\n"));
+            ranges.push(CharRange { start: idx, end });
+            idx = end;
+        } else {
+            // Move on by one character, which might span several bytes.
+            idx += rest.chars().next().map(|el| el.len_utf8()).unwrap_or(1);
+        }
+    }
+
+    ranges
+}
+
+/// Find the children of multi-line JSX elements, e.g. the text between `<TabItem value="a">` and
+/// `</TabItem>`. Such elements are parsed as HTML blocks unless their children are separated from
+/// the tags by empty lines. Children are runs of lines that do not start with a tag or expression.
+/// Lines with tags or expressions are kept as they are, even if text follows a tag on the same
+/// line. The returned ranges come with the indent of their lines, are sorted, and do not overlap.
+pub fn find_jsx_children(text: &str) -> Vec<(usize, CharRange)> {
+    let events = Parser::new(text).into_offset_iter().collect::<Vec<_>>();
+    let ignored = ignore::find_ignored_ranges(&events, text.len());
+    let mut quote_level: usize = 0;
+    let mut children = vec![];
+
+    for (event, range) in events {
+        match event {
+            // JSX elements in block quotes are handled when formatting the block quotes.
+            Event::Start(Tag::BlockQuote(..)) => quote_level += 1,
+            Event::End(TagEnd::BlockQuote(..)) => quote_level -= 1,
+            Event::Start(Tag::HtmlBlock)
+                if quote_level == 0
+                    && !ignored.iter().any(|el| el.contains(&range.start))
+                    && is_element_start(&text[range.clone()]) =>
+            {
+                children.extend(find_children_in_block(text, range));
+            }
+            _ => {}
+        }
+    }
+
+    children
+}
+
+/// Check whether an HTML block starts with a tag whose children are markdown.
+fn is_element_start(block: &str) -> bool {
+    let block = block.trim_start();
+    let name = block
+        .strip_prefix('<')
+        .unwrap_or("")
+        .chars()
+        .take_while(|ch| ch.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    is_tag_start(block) && !block.starts_with("</") && !RAW_TEXT_TAGS.contains(&name.as_str())
+}
+
+fn find_children_in_block(text: &str, block: CharRange) -> Vec<(usize, CharRange)> {
+    let mut children = vec![];
+    let mut child: Option<(usize, CharRange)> = None;
+    let mut line_start = block.start;
+
+    while line_start < block.end {
+        let line_end = text[line_start..block.end]
+            .find('\n')
+            .map_or(block.end, |el| line_start + el + 1);
+        let line = &text[line_start..line_end];
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let content = &line[indent..];
+
+        let tag_end = if is_tag_start(content) || content.starts_with('{') {
+            // Tags might span several lines. Lines that cannot be parsed are kept, too.
+            Some(
+                find_end(&text[line_start + indent..block.end]).map_or(line_end, |el| {
+                    let end = line_start + indent + el;
+                    text[end..block.end]
+                        .find('\n')
+                        .map_or(block.end, |el| end + el + 1)
+                }),
+            )
+        } else if content.trim().is_empty() {
+            Some(line_end)
+        } else {
+            None
+        };
+
+        if let Some(end) = tag_end {
+            children.extend(child.take());
+            line_start = end;
+        } else {
+            let (child_indent, child_range) = child.get_or_insert((indent, line_start..line_start));
+            *child_indent = indent.min(*child_indent);
+            child_range.end = line_end;
+            line_start = line_end;
+        }
+    }
+    children.extend(child);
+
+    for (_indent, range) in children.iter() {
+        trace_log!(
+            "found jsx children: {}",
+            text[range.clone()].replace('\n', "\\n")
+        );
+    }
+    children
+}
+
+/// Check whether "text" starts with a JSX tag, e.g. `<Tabs>`, `</Tabs>`, or a fragment `<>`.
+fn is_tag_start(text: &str) -> bool {
+    text.starts_with('<')
+        && text[1..]
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '/' || ch == '>')
+}
+
+/// Find the end of the tag or expression at the start of "text", taking quoted strings and
+/// nested braces into account. The returned index is just past the closing character.
+fn find_end(text: &str) -> Option<usize> {
+    let is_tag = text.starts_with('<');
+    let mut depth: usize = 0;
+    let mut quote = None;
+    let mut last_was_linebreak = false;
+
+    for (idx, ch) in text.char_indices() {
+        // Tags and expressions never span paragraphs.
+        if ch == '\n' && last_was_linebreak {
+            return None;
+        }
+        if ch != ' ' && ch != '\t' {
+            last_was_linebreak = ch == '\n';
+        }
+
+        match (quote, ch) {
+            (Some(q), _) if q == ch => quote = None,
+            (Some(_), _) => {}
+            // Quotes in text, e.g. apostrophes, are only considered in attributes or code.
+            (None, '"' | '\'' | '`') if is_tag || depth > 0 => quote = Some(ch),
+            (None, '{') => depth += 1,
+            (None, '}') => {
+                depth = depth.checked_sub(1)?;
+                if !is_tag && depth == 0 {
+                    return Some(idx + 1);
+                }
+            }
+            (None, '>') if is_tag && depth == 0 => return Some(idx + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finding_jsx_tags_and_expressions() {
+        let text = r#"import {Tabs} from '@theme/Tabs';
+
+<Tabs
+  groupId="a b"
+  values={[{label: 'A > B', value: 'a'}]}>
+<TabItem value="a">
+
+Some text with {props.x y} and <Badge text="one. two" /> but `<code a>` and a < b.
+
+</TabItem>
+</Tabs>
+
+An {unclosed expression.
+
+```
+<Fenced code>
+```
+"#;
+
+        let found = find_jsx(text)
+            .into_iter()
+            .map(|el| &text[el])
+            .collect::<Vec<_>>();
+
+        let expected = vec![
+            "{Tabs}",
+            "<Tabs\n  groupId=\"a b\"\n  values={[{label: 'A > B', value: 'a'}]}>",
+            "<TabItem value=\"a\">",
+            "{props.x y}",
+            "<Badge text=\"one. two\" />",
+        ];
+        assert_eq!(expected, found);
+    }
+
+    #[test]
+    fn finding_children_of_jsx_elements() {
+        let text = r#"<Tabs>
+<TabItem
+  value="a">
+  Some text.
+  More text.
+</TabItem>
+<TabItem value="b"><b>Same line.</b></TabItem>
+{/* A comment. */}
+Other text.
+</Tabs>
+
+<!-- mdslw-ignore-start -->
+<Tabs>
+Ignored text.
+</Tabs>
+<!-- mdslw-ignore-end -->
+
+<pre>
+Raw text.
+</pre>
+
+> <Quoted>
+> Quoted text.
+> </Quoted>
+"#;
+
+        let found = find_jsx_children(text)
+            .into_iter()
+            .map(|(indent, range)| (indent, &text[range]))
+            .collect::<Vec<_>>();
+
+        let expected = vec![(2, "  Some text.\n  More text.\n"), (0, "Other text.\n")];
+        assert_eq!(expected, found);
+    }
+}
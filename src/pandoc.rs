@@ -0,0 +1,143 @@
+/* An opinionated line wrapper for markdown files.
+Copyright (C) 2023  Torsten Long
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::parse::{self, CharRange};
+use crate::trace_log;
+
+/// Find bracketed citations, e.g. `[@smith2020, p. 33]`, and bracketed spans with attributes,
+/// e.g. `[some text]{.smallcaps}`, outside of code. The returned ranges are sorted and do not
+/// overlap.
+pub fn find_citations_and_spans(text: &str) -> Vec<CharRange> {
+    let code_ranges = parse::find_code_ranges(text);
+
+    let mut ranges = vec![];
+    let mut idx = 0;
+    // Index of the first code range that does not end before "idx".
+    let mut code_idx = 0;
+    while idx < text.len() {
+        while code_ranges.get(code_idx).is_some_and(|el| el.end <= idx) {
+            code_idx += 1;
+        }
+        if let Some(code) = code_ranges.get(code_idx).filter(|el| el.contains(&idx)) {
+            idx = code.end;
+            continue;
+        }
+
+        let rest = &text[idx..];
+        let end = if rest.starts_with('[') && !text[..idx].ends_with('\\') {
+            find_closing(rest, '[', ']').and_then(|end| {
+                let after = &rest[end..];
+                if after.starts_with('{') {
+                    // A bracketed span, whose attributes belong to it.
+                    find_closing(after, '{', '}').map(|el| end + el)
+                } else if !after.starts_with(['(', '[', ':']) && is_citation(&rest[1..end - 1]) {
+                    // A citation that is not a link or a link definition.
+                    Some(end)
+                } else {
+                    None
+                }
+            })
+        } else {
+            None
+        };
+
+        if let Some(end) = end {
+            let end = idx + end;
+            trace_log!(
+                "found citation or span: {}",
+                text[idx..end].replace('\n', "\\n")
+            );
+            ranges.push(CharRange { start: idx, end });
+            idx = end;
+        } else {
+            // Move on by one character, which might span several bytes.
+            idx += rest.chars().next().map(|el| el.len_utf8()).unwrap_or(1);
+        }
+    }
+
+    ranges
+}
+
+/// Find the end of the bracketed text at the start of "text", taking nested and escaped brackets
+/// into account. The returned index is just past the closing character. Brackets never span
+/// paragraphs.
+fn find_closing(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth: usize = 0;
+    let mut escaped = false;
+    let mut last_was_linebreak = false;
+
+    for (idx, ch) in text.char_indices() {
+        if ch == '\n' && last_was_linebreak {
+            return None;
+        }
+        if ch != ' ' && ch != '\t' {
+            last_was_linebreak = ch == '\n';
+        }
+
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if ch == open => depth += 1,
+            _ if ch == close => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(idx + ch.len_utf8());
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Check whether the content of a bracketed text contains a citation key, e.g. `@smith2020`. Keys
+/// start a word, which distinguishes them from email addresses.
+fn is_citation(content: &str) -> bool {
+    let chars = content.chars().collect::<Vec<_>>();
+    chars.iter().enumerate().any(|(idx, ch)| {
+        let starts_word =
+            idx == 0 || matches!(chars[idx - 1], '-' | ';' | '[') || chars[idx - 1].is_whitespace();
+        let has_key = chars
+            .get(idx + 1)
+            .is_some_and(|el| el.is_alphanumeric() || matches!(el, '_' | '{'));
+        ch == &'@' && starts_word && has_key
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finding_citations_and_spans() {
+        let text = "As shown [see @smith2020, p. 33; -@doe2021] and [some text]{.smallcaps key=\"a b\"},\n\
+                    but not [a link](url){.class}, [mail me](mailto:a@b.c), [an\n\nunclosed @x],\n\
+                    `[@code]`, or \\[@escaped], but [nested [@cite, ch. 2]] works.";
+
+        let found = find_citations_and_spans(text)
+            .into_iter()
+            .map(|el| &text[el])
+            .collect::<Vec<_>>();
+
+        let expected = vec![
+            "[see @smith2020, p. 33; -@doe2021]",
+            "[some text]{.smallcaps key=\"a b\"}",
+            "[nested [@cite, ch. 2]]",
+        ];
+        assert_eq!(expected, found);
+    }
+}
@@ -16,29 +16,63 @@ along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
 use core::ops::Range;
-use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use pulldown_cmark::{CodeBlockKind, Event, LinkType, Options, Parser, Tag, TagEnd};
 use std::collections::HashMap;
 use std::fmt::Write;
 
 use crate::detect::WhitespaceDetector;
 use crate::ignore::IgnoreByHtmlComment;
 use crate::indent::build_indent;
+use crate::mdx;
+use crate::template::TemplateDelimiters;
 use crate::trace_log;
 
 const YAML_CONFIG_KEY: &str = "mdslw-toml";
 const YAML_CONFIG_KEY_WITH_COLON: &str = "mdslw-toml:";
+// Admonitions and similar MyST or Sphinx directives whose bodies are markdown.
+const MARKDOWN_DIRECTIVES: [&str; 18] = [
+    "admonition",
+    "attention",
+    "caution",
+    "danger",
+    "deprecated",
+    "dropdown",
+    "error",
+    "hint",
+    "important",
+    "margin",
+    "note",
+    "seealso",
+    "sidebar",
+    "tip",
+    "todo",
+    "versionadded",
+    "versionchanged",
+    "warning",
+];
 
 /// CharRange describes a range of characters in a document.
 pub type CharRange = Range<usize>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct ParseCfg {
     pub keep_linebreaks: bool,
     pub keep_colon_fences: bool,
+    pub format_admonitions: bool,
+    pub mdx: bool,
+    pub wiki_links: bool,
+    pub pandoc: bool,
+    pub tasklists: bool,
+    pub heading_attributes: bool,
+    pub strikethrough: bool,
 }
 
 /// Determine ranges of characters that shall later be wrapped and have their indents fixed.
-pub fn parse_markdown(text: &str, parse_cfg: &ParseCfg) -> Vec<CharRange> {
+pub fn parse_markdown(
+    text: &str,
+    parse_cfg: &ParseCfg,
+    templates: &TemplateDelimiters,
+) -> Vec<CharRange> {
     // Enable some options by default to support parsing common kinds of documents.
     let mut opts = Options::empty();
     // If we do not want to modify some elements, we detect them with the parser and consider them
@@ -47,12 +81,28 @@ pub fn parse_markdown(text: &str, parse_cfg: &ParseCfg) -> Vec<CharRange> {
     opts.insert(Options::ENABLE_TABLES);
     log::debug!("detecting definition lists");
     opts.insert(Options::ENABLE_DEFINITION_LIST);
+    if parse_cfg.wiki_links {
+        log::debug!("detecting wiki links");
+        opts.insert(Options::ENABLE_WIKILINKS);
+    }
+    // Enable some options only if desired.
+    if parse_cfg.tasklists {
+        // Text after a task's checkbox is indented relative to the text, not the list marker.
+        log::debug!("detecting task lists");
+        opts.insert(Options::ENABLE_TASKLISTS);
+    }
+    if parse_cfg.heading_attributes {
+        log::debug!("detecting heading attributes");
+        opts.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    }
+    if parse_cfg.strikethrough {
+        // Strikethrough text is treated as atomic, see AtomicMask.
+        log::debug!("detecting strikethrough");
+        opts.insert(Options::ENABLE_STRIKETHROUGH);
+    }
     // Do not enable other options:
     // opts.insert(Options::ENABLE_FOOTNOTES);
-    // opts.insert(Options::ENABLE_TASKLISTS);
-    // opts.insert(Options::ENABLE_HEADING_ATTRIBUTES);
     // opts.insert(Options::ENABLE_SMART_PUNCTUATION);
-    // opts.insert(Options::ENABLE_STRIKETHROUGH);
     let events_and_ranges = Parser::new_ext(text, opts)
         .into_offset_iter()
         .inspect(|(event, range)| {
@@ -61,14 +111,29 @@ pub fn parse_markdown(text: &str, parse_cfg: &ParseCfg) -> Vec<CharRange> {
         .collect::<Vec<_>>();
     let whitespaces = whitespace_indices(text, &WhitespaceDetector::new(parse_cfg.keep_linebreaks));
 
-    let colon_fenced_ranges = if parse_cfg.keep_colon_fences {
+    let mut verbatim_ranges = if parse_cfg.keep_colon_fences {
         find_colon_fenced_ranges(text)
     } else {
         vec![]
     };
+    // Admonitions are formatted separately. Thus, they are kept verbatim here.
+    if parse_cfg.format_admonitions {
+        verbatim_ranges.extend(find_admonitions(text).into_iter().map(|el| el.range));
+    }
+    // Lines with nothing but template code usually contain block-level tags, e.g. shortcodes.
+    verbatim_ranges.extend(templates.find_lines(text));
+    if parse_cfg.mdx {
+        verbatim_ranges.extend(find_esm_ranges(text, &events_and_ranges));
+    }
+    if parse_cfg.wiki_links {
+        verbatim_ranges.extend(find_wiki_embed_lines(text, &events_and_ranges));
+    }
+    if parse_cfg.pandoc {
+        verbatim_ranges.extend(find_line_block_ranges(text, &events_and_ranges));
+    }
 
     merge_ranges(
-        to_be_wrapped(events_and_ranges, &whitespaces, &colon_fenced_ranges),
+        to_be_wrapped(events_and_ranges, &whitespaces, &verbatim_ranges),
         &whitespaces,
     )
 }
@@ -78,14 +143,13 @@ pub fn parse_markdown(text: &str, parse_cfg: &ParseCfg) -> Vec<CharRange> {
 fn to_be_wrapped(
     events: Vec<(Event, CharRange)>,
     whitespaces: &HashMap<usize, char>,
-    colon_fenced_ranges: &[CharRange],
+    verbatim_ranges: &[CharRange],
 ) -> Vec<CharRange> {
     let mut verbatim_level: usize = 0;
     let mut ignore = IgnoreByHtmlComment::new();
 
-    let is_in_colon_fence = |pos: &usize| {
-        !colon_fenced_ranges.is_empty()
-            && colon_fenced_ranges.iter().any(|range| range.contains(pos))
+    let is_verbatim = |pos: &usize| {
+        !verbatim_ranges.is_empty() && verbatim_ranges.iter().any(|range| range.contains(pos))
     };
 
     events
@@ -100,6 +164,15 @@ fn to_be_wrapped(
         .filter(|(event, range)| match event {
             Event::Start(tag) => {
                 match tag {
+                    // Wiki embeds are treated like links, i.e. they are moved around as a whole.
+                    Tag::Image {
+                        link_type: LinkType::WikiLink { .. },
+                        ..
+                    } => {
+                        let wrap = verbatim_level == 0 && !is_verbatim(&range.start);
+                        verbatim_level += 1;
+                        wrap
+                    }
                     // Most delimited blocks should stay as they are. Introducing line breaks would
                     // cause problems here.
                     Tag::BlockQuote(..)
@@ -118,9 +191,11 @@ fn to_be_wrapped(
                     }
                     // In case of some blocks, we do not want to extract the text contained inside
                     // them but keep everything the block encompasses.
+                    // Inside verbatim blocks, e.g. headings, they are kept as they are, too.
                     Tag::Emphasis | Tag::Link { .. } | Tag::Strikethrough | Tag::Strong => {
+                        let wrap = verbatim_level == 0 && !is_verbatim(&range.start);
                         verbatim_level += 1;
-                        !is_in_colon_fence(&range.start)
+                        wrap
                     }
                     // Other delimited blocks can be both, inside a verbatim block or inside text.
                     // However, the text they embrace is the important bit but we do not want to
@@ -199,7 +274,7 @@ fn to_be_wrapped(
             // that also includes blocks that are extracted in their enirey (e.g. links). In the
             // context of text contained within, they cound as verbatim blocks, too.
             Event::SoftBreak | Event::HardBreak | Event::Text(..) | Event::Code(..) => {
-                verbatim_level == 0 && !is_in_colon_fence(&range.start)
+                verbatim_level == 0 && !is_verbatim(&range.start)
             }
         })
         .map(|(_event, range)| range)
@@ -212,6 +287,13 @@ enum RangeMatch<'a> {
     NoMatch(&'a str),
 }
 
+/// Determine the column, i.e. the indent, of every byte in the text.
+fn indents(text: &str) -> Vec<usize> {
+    text.split_inclusive('\n')
+        .flat_map(|line| 0..line.len())
+        .collect::<Vec<_>>()
+}
+
 pub struct BlockQuotes<'a>(Vec<RangeMatch<'a>>);
 
 impl<'a> BlockQuotes<'a> {
@@ -221,11 +303,22 @@ impl<'a> BlockQuotes<'a> {
 
     fn strip_prefix(text: &str, indent: usize) -> String {
         // The first line does start with the actual prefix, while the other lines start with a
-        // number of other characters. Thus, we strip the off for all but the first line.
+        // number of spaces. Thus, we strip them off for all but the first line. Other lines might
+        // be indented differently, e.g. lazy continuation lines that lack the prefix, or they might
+        // be shorter than the indent. Thus, we only strip off leading spaces.
         text.split_inclusive('\n')
             .enumerate()
             .map(|(idx, t)| {
-                let t = if idx == 0 { t } else { &t[indent..t.len()] };
+                let t = if idx == 0 {
+                    t
+                } else {
+                    let trimmed = t.trim_start_matches(' ');
+                    if trimmed.starts_with(Self::SHORT_PREFIX) {
+                        trimmed
+                    } else {
+                        &t[indent.min(t.len() - trimmed.len())..]
+                    }
+                };
                 t.strip_prefix(Self::SHORT_PREFIX)
                     .map(|el| el.strip_prefix(' ').unwrap_or(el))
                     .unwrap_or(t)
@@ -240,7 +333,7 @@ impl<'a> BlockQuotes<'a> {
         text.split_inclusive('\n')
             .enumerate()
             .for_each(|(idx, line)| {
-                let prefix = if line.len() == 1 {
+                let prefix = if line.trim().is_empty() {
                     Self::SHORT_PREFIX
                 } else {
                     Self::FULL_PREFIX
@@ -254,10 +347,23 @@ impl<'a> BlockQuotes<'a> {
         result
     }
 
-    fn indents(text: &str) -> Vec<usize> {
-        text.split_inclusive('\n')
-            .flat_map(|line| 0..line.len())
-            .collect::<Vec<_>>()
+    /// Split off the header line of a GitHub alert, e.g. `[!NOTE]`, or of an Obsidian callout,
+    /// e.g. `[!tip]- Some title`. That line has to be kept as is. Text without such a header is
+    /// returned as the body.
+    fn split_alert_header(text: &str) -> (&str, &str) {
+        let header_len = text.find('\n').map(|el| el + 1).unwrap_or(text.len());
+        let is_alert_header = text
+            .trim_start_matches(' ')
+            .strip_prefix("[!")
+            .and_then(|el| el.split_once(']'))
+            .is_some_and(|(kind, _title)| {
+                !kind.is_empty() && kind.chars().all(|ch| ch.is_alphanumeric() || ch == '-')
+            });
+        if is_alert_header {
+            text.split_at(header_len)
+        } else {
+            ("", text)
+        }
     }
 
     pub fn new(text: &'a str) -> Self {
@@ -265,7 +371,7 @@ impl<'a> BlockQuotes<'a> {
         // In case we ever need to iterate over other kinds of syntax, the tag as well as the
         // function stripping prefixes will have to be adjusted.
 
-        let indents = Self::indents(text);
+        let indents = indents(text);
         let mut start = 0;
 
         let mut ranges = Parser::new(text)
@@ -327,10 +433,16 @@ impl<'a> BlockQuotes<'a> {
             .into_iter()
             .map(|el| match el {
                 RangeMatch::NoMatch(s) => s.to_string(),
-                RangeMatch::Matches(s) => Self::add_prefix(
-                    func(Self::strip_prefix(s.1, s.0), s.0 + Self::FULL_PREFIX_LEN),
-                    s.0,
-                ),
+                RangeMatch::Matches(s) => {
+                    let stripped = Self::strip_prefix(s.1, s.0);
+                    let (header, body) = Self::split_alert_header(&stripped);
+                    let formatted = if body.is_empty() {
+                        String::new()
+                    } else {
+                        func(body.to_string(), s.0 + Self::FULL_PREFIX_LEN)
+                    };
+                    Self::add_prefix(format!("{}{}", header, formatted), s.0)
+                }
             })
             .collect::<String>()
     }
@@ -504,6 +616,435 @@ pub fn get_value_for_mdslw_toml_yaml_key(text: &str) -> String {
     }
 }
 
+/// Extract the value of a top-level YAML key whose value is a plain or quoted scalar on the same
+/// line as the key, e.g. "key: value" or "key: 'value'". Like above, this does not require a full
+/// YAML parser. Block scalars and nested keys are not supported.
+pub fn get_scalar_value_for_yaml_key(text: &str, key: &str) -> Option<String> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix(key))
+        .filter_map(|rest| rest.trim_start().strip_prefix(':'))
+        // Remove a possible trailing comment. We do not support a '#' inside values.
+        .map(|value| value.split(" #").next().unwrap_or(value).trim())
+        .map(|value| {
+            value
+                .strip_prefix('"')
+                .and_then(|el| el.strip_suffix('"'))
+                .or_else(|| {
+                    value
+                        .strip_prefix('\'')
+                        .and_then(|el| el.strip_suffix('\''))
+                })
+                .unwrap_or(value)
+        })
+        .find(|value| !value.is_empty())
+        .map(String::from)
+}
+
+/// DirectiveFences provides access to the bodies of MyST directives using backtick fences, e.g.
+/// ```` ```{note} ````. The fences and any option lines, i.e. lines like `:class: tip` directly
+/// following the opening fence or a YAML block delimited by `---`, are kept as they are.
+pub struct DirectiveFences<'a>(Vec<RangeMatch<'a>>);
+
+impl<'a> DirectiveFences<'a> {
+    /// Check whether the info string of a fence belongs to a directive whose body is markdown, e.g.
+    /// "{note}" or "{admonition} Title". Other directives, e.g. "{code-cell}" or "{math}", and code
+    /// chunks like "{r}" contain code and are kept as they are.
+    fn is_directive(info: &str) -> bool {
+        info.strip_prefix('{')
+            .and_then(|el| el.split_once('}'))
+            .is_some_and(|(name, _args)| MARKDOWN_DIRECTIVES.contains(&name.trim()))
+    }
+
+    /// Split a fenced code block into the opening fence including option lines, the body, and the
+    /// closing fence. The closing fence might be missing at the end of the document.
+    fn split_block(block: &str) -> (usize, usize) {
+        let lines = block.split_inclusive('\n').collect::<Vec<_>>();
+        let fence = lines[0]
+            .trim_start()
+            .chars()
+            .take_while(|ch| ch == &'`' || ch == &'~')
+            .collect::<String>();
+        let is_closing_fence = |line: &&str| {
+            let trimmed = line.trim();
+            trimmed.len() >= fence.len() && trimmed.chars().all(|ch| fence.starts_with(ch))
+        };
+        let num_closing =
+            usize::from(lines.len() > 1 && lines.last().is_some_and(is_closing_fence));
+        let body_lines = &lines[1..lines.len() - num_closing];
+
+        let num_options = if body_lines.first().is_some_and(|el| el.trim() == "---") {
+            body_lines
+                .iter()
+                .skip(1)
+                .position(|el| el.trim() == "---")
+                .map(|el| el + 2)
+                .unwrap_or(0)
+        } else {
+            body_lines
+                .iter()
+                .take_while(|el| el.trim_start().starts_with(':'))
+                .count()
+        };
+
+        let body_start = lines[..1 + num_options]
+            .iter()
+            .map(|el| el.len())
+            .sum::<usize>();
+        let body_end = block.len()
+            - lines[lines.len() - num_closing..]
+                .iter()
+                .map(|el| el.len())
+                .sum::<usize>();
+        (body_start, body_end.max(body_start))
+    }
+
+    pub fn new(text: &'a str) -> Self {
+        let indents = indents(text);
+        let mut quote_level: usize = 0;
+
+        let ranges = Parser::new_ext(text, Options::ENABLE_TABLES)
+            .into_offset_iter()
+            .filter_map(|(event, range)| match event {
+                // Directives in block quotes are handled when formatting the block quotes.
+                Event::Start(Tag::BlockQuote(..)) => {
+                    quote_level += 1;
+                    None
+                }
+                Event::End(TagEnd::BlockQuote(..)) => {
+                    quote_level -= 1;
+                    None
+                }
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                    if quote_level == 0 && Self::is_directive(&info) =>
+                {
+                    let block = &text[range.clone()];
+                    let indent = indents[range.start] + block.len() - block.trim_start().len();
+                    let (body_start, body_end) = Self::split_block(block);
+                    trace_log!(
+                        "found directive body: {}",
+                        text[range.start + body_start..range.start + body_end].replace('\n', "\\n")
+                    );
+                    Some((
+                        indent,
+                        CharRange {
+                            start: range.start + body_start,
+                            end: range.start + body_end,
+                        },
+                    ))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        Self(to_range_matches(text, ranges))
+    }
+
+    /// The argument `func` receives the body without its indent and should keep a line break at
+    /// the end if its arguments ends in one.
+    pub fn apply_to_matches_and_join<MapFn>(self, func: MapFn) -> String
+    where
+        MapFn: Fn(String, usize) -> String,
+    {
+        apply_to_indented_matches_and_join(self.0, func)
+    }
+}
+
+fn strip_indent(text: &str, indent: usize) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            let num_spaces = line.len() - line.trim_start_matches(' ').len();
+            &line[indent.min(num_spaces)..]
+        })
+        .collect::<String>()
+}
+
+fn add_indent(text: String, indent: usize) -> String {
+    let indent = build_indent(indent);
+    text.split_inclusive('\n')
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.to_string()
+            } else {
+                format!("{}{}", indent, line)
+            }
+        })
+        .collect::<String>()
+}
+
+/// Apply "func" to all matches after removing their indent, which is restored afterwards.
+fn apply_to_indented_matches_and_join<MapFn>(ranges: Vec<RangeMatch>, func: MapFn) -> String
+where
+    MapFn: Fn(String, usize) -> String,
+{
+    ranges
+        .into_iter()
+        .map(|el| match el {
+            RangeMatch::NoMatch(s) => s.to_string(),
+            RangeMatch::Matches(s) => add_indent(func(strip_indent(s.1, s.0), s.0), s.0),
+        })
+        .collect::<String>()
+}
+
+/// Convert ranges with indents into matches, filling the gaps in between with non-matches. The
+/// ranges have to be sorted and must not overlap.
+fn to_range_matches<'a, I>(text: &'a str, ranges: I) -> Vec<RangeMatch<'a>>
+where
+    I: IntoIterator<Item = (usize, CharRange)>,
+{
+    let mut start = 0;
+    let mut matches = ranges
+        .into_iter()
+        .filter(|(_indent, range)| !range.is_empty())
+        .flat_map(|(indent, range)| {
+            let prev_start = start;
+            let this_start = range.start;
+            start = range.end;
+
+            let this = RangeMatch::Matches((indent, &text[range]));
+            if this_start == prev_start {
+                vec![this]
+            } else {
+                let missing = RangeMatch::NoMatch(&text[prev_start..this_start]);
+                vec![missing, this]
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if start != text.len() {
+        matches.push(RangeMatch::NoMatch(&text[start..text.len()]));
+    }
+    matches
+}
+
+#[derive(Debug, PartialEq)]
+struct Admonition {
+    // The range of the entire admonition, including the header line.
+    range: CharRange,
+    // The range of the body, i.e. all indented lines following the header line.
+    body: CharRange,
+    // The indent of the body.
+    indent: usize,
+}
+
+/// Find Python-Markdown or MkDocs admonitions, e.g. `!!! note "Title"` or `??? tip`, followed by
+/// an indented body. Admonitions in block quotes are not detected.
+fn find_admonitions(text: &str) -> Vec<Admonition> {
+    const MARKERS: [&str; 3] = ["!!! ", "??? ", "???+ "];
+    const BODY_INDENT: usize = 4;
+
+    let indents = indents(text);
+    let mut quote_level: usize = 0;
+    let mut in_code_block = false;
+    // Admonitions can be nested. Nested ones are processed when formatting their parents' bodies.
+    let mut last_end = 0;
+
+    Parser::new_ext(text, Options::ENABLE_TABLES)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::BlockQuote(..)) => {
+                quote_level += 1;
+                None
+            }
+            Event::End(TagEnd::BlockQuote(..)) => {
+                quote_level -= 1;
+                None
+            }
+            Event::Start(Tag::CodeBlock(..)) => {
+                in_code_block = true;
+                None
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                in_code_block = false;
+                None
+            }
+            // Admonition headers are parsed as the start of a paragraph or, in tight lists, as
+            // text at the start of a line, possibly following a list marker.
+            Event::Start(Tag::Paragraph) | Event::Text(..)
+                if quote_level == 0
+                    && !in_code_block
+                    && range.start >= last_end
+                    && text[..range.start].rsplit('\n').next().is_some_and(|el| {
+                        el.chars()
+                            .all(|ch| " -*+.)".contains(ch) || ch.is_ascii_digit())
+                    }) =>
+            {
+                let header_end = text[range.start..]
+                    .find('\n')
+                    .map(|el| range.start + el + 1)?;
+                let header = &text[range.start..header_end];
+                if !MARKERS.iter().any(|el| header.starts_with(el)) {
+                    return None;
+                }
+
+                let indent = indents[range.start] + BODY_INDENT;
+                let mut body_end = header_end;
+                let mut end = header_end;
+                for line in text[header_end..].split_inclusive('\n') {
+                    let num_spaces = line.len() - line.trim_start_matches(' ').len();
+                    if line.trim().is_empty() {
+                        end += line.len();
+                    } else if num_spaces >= indent {
+                        end += line.len();
+                        body_end = end;
+                    } else {
+                        break;
+                    }
+                }
+                last_end = body_end;
+                trace_log!(
+                    "found admonition: {}",
+                    text[range.start..body_end].replace('\n', "\\n")
+                );
+
+                Some(Admonition {
+                    range: range.start..body_end,
+                    body: header_end..body_end,
+                    indent,
+                })
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Admonitions provides access to the bodies of Python-Markdown or MkDocs admonitions. The header
+/// lines, e.g. `!!! note "Title"`, are kept as they are.
+pub struct Admonitions<'a>(Vec<RangeMatch<'a>>);
+
+impl<'a> Admonitions<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let ranges = find_admonitions(text)
+            .into_iter()
+            .map(|el| (el.indent, el.body));
+        Self(to_range_matches(text, ranges))
+    }
+
+    /// The argument `func` receives the body without its indent and should keep a line break at
+    /// the end if its arguments ends in one.
+    pub fn apply_to_matches_and_join<MapFn>(self, func: MapFn) -> String
+    where
+        MapFn: Fn(String, usize) -> String,
+    {
+        apply_to_indented_matches_and_join(self.0, func)
+    }
+}
+
+/// JsxChildren provides access to the children of multi-line JSX elements in MDX documents, e.g.
+/// the markdown text between `<TabItem value="a">` and `</TabItem>`. The lines with tags are kept
+/// as they are.
+pub struct JsxChildren<'a>(Vec<RangeMatch<'a>>);
+
+impl<'a> JsxChildren<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self(to_range_matches(text, mdx::find_jsx_children(text)))
+    }
+
+    /// The argument `func` receives the children without their indent and should keep a line
+    /// break at the end if its arguments ends in one.
+    pub fn apply_to_matches_and_join<MapFn>(self, func: MapFn) -> String
+    where
+        MapFn: Fn(String, usize) -> String,
+    {
+        apply_to_indented_matches_and_join(self.0, func)
+    }
+}
+
+/// Find ranges of inline code spans and code blocks, which must not be modified by any syntax
+/// extension detected outside of the parser.
+pub fn find_code_ranges(text: &str) -> Vec<CharRange> {
+    Parser::new(text)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(..) | Event::Start(Tag::CodeBlock(..)) => Some(range),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Find ranges of ESM statements in MDX documents, i.e. of top-level paragraphs starting with an
+/// "import" or "export" keyword. Those are JavaScript and not markdown. A paragraph followed by a
+/// line starting with a colon is parsed as the title of a definition list, which counts, too.
+fn find_esm_ranges(text: &str, events: &[(Event, CharRange)]) -> Vec<CharRange> {
+    let mut level: usize = 0;
+    let mut in_definition_list = false;
+    events
+        .iter()
+        .filter_map(|(event, range)| {
+            let is_top_level = level == 0 || (level == 1 && in_definition_list);
+            match event {
+                Event::Start(tag) => {
+                    if level == 0 {
+                        in_definition_list = matches!(tag, Tag::DefinitionList);
+                    }
+                    level += 1;
+                }
+                Event::End(..) => level = level.saturating_sub(1),
+                _ => {}
+            }
+            match event {
+                Event::Start(Tag::Paragraph | Tag::DefinitionListTitle) if is_top_level => {
+                    Some(range.clone())
+                }
+                _ => None,
+            }
+        })
+        .filter(|range| {
+            let words = &mut text[range.clone()].split_whitespace();
+            matches!(words.next(), Some("import" | "export")) && words.next().is_some()
+        })
+        .inspect(|range| trace_log!("found esm: {}", &text[range.clone()]))
+        .collect::<Vec<_>>()
+}
+
+/// Find lines that contain nothing but a wiki embed, e.g. `![[image.png]]`, including the line
+/// breaks that separate them from the lines around them. Such lines are kept as they are.
+fn find_wiki_embed_lines(text: &str, events: &[(Event, CharRange)]) -> Vec<CharRange> {
+    events
+        .iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Image {
+                link_type: LinkType::WikiLink { .. },
+                ..
+            }) => {
+                let line_start = text[..range.start].rfind('\n').map_or(0, |el| el + 1);
+                let line_end = text[range.end..]
+                    .find('\n')
+                    .map(|el| range.end + el + 1)
+                    .unwrap_or(text.len());
+                let is_alone = text[line_start..range.start].trim().is_empty()
+                    && text[range.end..line_end].trim().is_empty();
+                is_alone.then(|| {
+                    trace_log!("found wiki embed line: {}", &text[range.clone()]);
+                    CharRange {
+                        start: line_start.saturating_sub(1),
+                        end: line_end,
+                    }
+                })
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Find ranges of Pandoc line blocks, i.e. of paragraphs whose first line starts with a vertical
+/// bar followed by a space. Line breaks in line blocks are significant, e.g. in poems or addresses.
+/// A line block followed by a line starting with a colon, e.g. a fenced div, is parsed as the
+/// title of a definition list, which counts, too.
+fn find_line_block_ranges(text: &str, events: &[(Event, CharRange)]) -> Vec<CharRange> {
+    events
+        .iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Paragraph | Tag::DefinitionListTitle) => Some(range.clone()),
+            _ => None,
+        })
+        .filter(|range| {
+            let first_line = text[range.clone()].lines().next().unwrap_or_default();
+            first_line == "|" || first_line.starts_with("| ")
+        })
+        .inspect(|range| trace_log!("found line block: {}", &text[range.clone()]))
+        .collect::<Vec<_>>()
+}
+
 /// Find char ranges that are inside a colon fence, including the fence itself. The returned ranges
 /// are guaranteed to be mutually exclusive. Their starting points are guaranteed to be strictly
 /// monotonically increasing.
@@ -628,11 +1169,8 @@ some code
 
 [link]: https://something.com "some link"
 "#;
-        let cfg = ParseCfg {
-            keep_linebreaks: false,
-            keep_colon_fences: false,
-        };
-        let parsed = parse_markdown(text, &cfg);
+        let cfg = ParseCfg::default();
+        let parsed = parse_markdown(text, &cfg, &TemplateDelimiters::default());
 
         // [18..28, 52..62, 65..75, 80..95, 100..124]
         let expected = vec![
@@ -649,6 +1187,147 @@ some code
         assert_eq!(expected, parsed);
     }
 
+    #[test]
+    fn parsing_markdown_inside_html_blocks() {
+        // Markdown content that is separated from HTML tags by blank lines is no part of the HTML
+        // blocks. Text that directly follows a tag is part of an HTML block and kept verbatim.
+        let text = r#"<details>
+<summary>Some summary</summary>
+
+Some text.
+
+</details>
+
+<div markdown="1">
+Verbatim text.
+
+More text.
+</div>
+"#;
+        let cfg = ParseCfg::default();
+        let parsed = parse_markdown(text, &cfg, &TemplateDelimiters::default());
+
+        let expected = vec![
+            CharRange { start: 43, end: 53 },
+            CharRange {
+                start: 102,
+                end: 112,
+            },
+        ];
+
+        assert_eq!(expected, parsed);
+        assert_eq!("Some text.", &text[43..53]);
+        assert_eq!("More text.", &text[102..112]);
+    }
+
+    #[test]
+    fn keeping_esm_statements_verbatim() {
+        let text = r#"import Tabs from '@theme/Tabs';
+export const meta = {title: 'A'};
+
+Some text about
+imports.
+
+- import this
+
+export const b = 1;
+: Some definition.
+"#;
+        let cfg = ParseCfg {
+            mdx: true,
+            ..Default::default()
+        };
+        let parsed = parse_markdown(text, &cfg, &TemplateDelimiters::default());
+
+        let expected = vec![
+            CharRange { start: 67, end: 91 },
+            CharRange {
+                start: 95,
+                end: 106,
+            },
+            CharRange {
+                start: 130,
+                end: 146,
+            },
+        ];
+
+        assert_eq!(expected, parsed);
+        assert_eq!("Some text about\nimports.", &text[67..91]);
+        assert_eq!("import this", &text[95..106]);
+        assert_eq!("Some definition.", &text[130..146]);
+    }
+
+    #[test]
+    fn keeping_lines_with_only_wiki_embeds_verbatim() {
+        let text = "Some text\n![[image.png]]\nmore ![[inline.png]] text.\n";
+        let cfg = ParseCfg {
+            wiki_links: true,
+            ..Default::default()
+        };
+        let parsed = parse_markdown(text, &cfg, &TemplateDelimiters::default());
+
+        let expected = vec![
+            CharRange { start: 0, end: 9 },
+            CharRange { start: 25, end: 51 },
+        ];
+
+        assert_eq!(expected, parsed);
+        assert_eq!("Some text", &text[0..9]);
+        assert_eq!("more ![[inline.png]] text.", &text[25..51]);
+    }
+
+    #[test]
+    fn keeping_line_blocks_verbatim() {
+        let text = "| Some poem\n| with lines\n\nSome text\n| with a bar.\n\n| A poem\n| before a div\n\n:::\nText.\n:::\n";
+        let cfg = ParseCfg {
+            pandoc: true,
+            keep_colon_fences: true,
+            ..Default::default()
+        };
+        let parsed = parse_markdown(text, &cfg, &TemplateDelimiters::default());
+
+        let expected = vec![
+            CharRange { start: 26, end: 49 },
+            CharRange { start: 80, end: 85 },
+        ];
+
+        assert_eq!(expected, parsed);
+        assert_eq!("Some text\n| with a bar.", &text[26..49]);
+        assert_eq!("Text.", &text[80..85]);
+    }
+
+    #[test]
+    fn parsing_tasks_and_strikethrough() {
+        let text = "- [ ] Some task.\n\nSome ~~struck~~ text.\n";
+        let cfg = ParseCfg {
+            tasklists: true,
+            strikethrough: true,
+            ..Default::default()
+        };
+        let parsed = parse_markdown(text, &cfg, &TemplateDelimiters::default());
+
+        let expected = vec![
+            CharRange { start: 6, end: 16 },
+            CharRange { start: 18, end: 39 },
+        ];
+
+        assert_eq!(expected, parsed);
+        assert_eq!("Some task.", &text[6..16]);
+        assert_eq!("Some ~~struck~~ text.", &text[18..39]);
+    }
+
+    #[test]
+    fn keeping_links_and_emphasis_in_headings_verbatim() {
+        let text = "# Title [a](b) *c*\n\n| [d](e) |\n|---|\n\nSome [text](link).\n";
+        let cfg = ParseCfg::default();
+        let parsed = parse_markdown(text, &cfg, &TemplateDelimiters::default());
+
+        let expected = vec![CharRange { start: 38, end: 56 }];
+
+        assert_eq!(expected, parsed);
+        assert_eq!("Some [text](link).", &text[38..56]);
+    }
+
     #[test]
     fn applying_to_no_block_quotes_remains_unchanged() {
         let text = r#"
@@ -750,6 +1429,193 @@ Some text with block quotes.
         assert_eq!(expected, changed);
     }
 
+    #[test]
+    fn applying_to_nested_and_lazy_block_quotes() {
+        let text = "- item\n\n  > Quote.\nlazy line.\nx\n    > indented.\n\n>quote\n>> nested\n> lazy\n\n> a\n> > b\n> > > c\n> > > d";
+
+        // Process nested block quotes recursively like the actual formatter does.
+        fn recurse(text: String, _indent: usize) -> String {
+            BlockQuotes::new(&text).apply_to_matches_and_join(recurse)
+        }
+        let changed = recurse(text.to_string(), 0);
+
+        let expected = "- item\n\n  > Quote.\n  > lazy line.\n  > x\n  > indented.\n\n> quote\n> > nested\n> > lazy\n\n> a\n> > b\n> > > c\n> > > d";
+        assert_eq!(expected, changed);
+    }
+
+    #[test]
+    fn applying_to_directive_bodies() {
+        let text = r#"
+Some text.
+
+```{note}
+:class: tip
+:name: some-name
+
+Some note.
+```
+
+- Item.
+
+  ````{admonition} Title
+  ---
+  class: tip
+  ---
+  Indented text.
+    More text.
+  ````
+
+```python
+code
+```
+
+```{tip}
+```
+
+```{code-cell} python
+import os; x = 1.
+y = 2
+```
+
+```{math}
+a = b. c = d
+```
+
+```{r}
+#| label: fig-a
+plot(x)
+```
+
+> ```{note}
+> Quoted.
+> ```
+"#;
+
+        let expected = r#"
+Some text.
+
+```{note}
+:class: tip
+:name: some-name
+0:12
+0:12
+```
+
+- Item.
+
+  ````{admonition} Title
+  ---
+  class: tip
+  ---
+  2:28
+  2:28
+  ````
+
+```python
+code
+```
+
+```{tip}
+```
+
+```{code-cell} python
+import os; x = 1.
+y = 2
+```
+
+```{math}
+a = b. c = d
+```
+
+```{r}
+#| label: fig-a
+plot(x)
+```
+
+> ```{note}
+> Quoted.
+> ```
+"#;
+
+        let changed = DirectiveFences::new(text)
+            .apply_to_matches_and_join(|s, i| format!("{}:{}\n{}:{}\n", i, s.len(), i, s.len()));
+        assert_eq!(expected, changed);
+    }
+
+    #[test]
+    fn applying_to_admonition_bodies() {
+        let text = r#"Some text.
+
+!!! note "Title"
+    Some note.
+
+    !!! tip
+        Nested.
+
+Not in the admonition.
+
+    !!! note
+        Indented code.
+
+- ??? warning
+      Indented.
+  Not in the admonition.
+"#;
+
+        let expected = r#"Some text.
+
+!!! note "Title"
+    4:32
+    4:32
+
+Not in the admonition.
+
+    !!! note
+        Indented code.
+
+- ??? warning
+      6:10
+      6:10
+  Not in the admonition.
+"#;
+
+        let changed = Admonitions::new(text)
+            .apply_to_matches_and_join(|s, i| format!("{}:{}\n{}:{}\n", i, s.len(), i, s.len()));
+        assert_eq!(expected, changed);
+    }
+
+    #[test]
+    fn keeping_alert_headers_in_block_quotes() {
+        let text = r#"
+> [!NOTE]
+> Some text.
+
+> [!tip]- Some title. Here
+> Some text.
+
+> [!WARNING]
+
+> [!not an alert]
+> Some text.
+"#;
+
+        let expected = r#"
+> [!NOTE]
+> 10
+
+> [!tip]- Some title. Here
+> 10
+
+> [!WARNING]
+
+> 26
+"#;
+
+        let changed = BlockQuotes::new(text)
+            .apply_to_matches_and_join(|s, _i| format!("{}\n", s.trim_end().len()));
+        assert_eq!(expected, changed);
+    }
+
     #[test]
     fn flattening_vecs_of_char_ranges_retains_ranges() {
         let to_be_flattened = vec![
@@ -877,6 +1743,24 @@ at the beginning and in the middle"#;
         assert_eq!(extracted, "".to_string());
     }
 
+    #[test]
+    fn extracting_scalar_yaml_values() {
+        let yaml = "title: Some Title\nlanguage:  'fr' # comment\nlang: \"de\"\n  nested: en\n";
+        assert_eq!(
+            get_scalar_value_for_yaml_key(yaml, "lang"),
+            Some("de".to_string())
+        );
+        assert_eq!(
+            get_scalar_value_for_yaml_key(yaml, "language"),
+            Some("fr".to_string())
+        );
+        assert_eq!(
+            get_scalar_value_for_yaml_key(yaml, "title"),
+            Some("Some Title".to_string())
+        );
+        assert_eq!(get_scalar_value_for_yaml_key(yaml, "nested"), None);
+    }
+
     #[test]
     fn config_keys_are_identical() {
         assert_eq!(
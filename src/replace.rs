@@ -15,15 +15,26 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use pulldown_cmark::{CowStr::Borrowed, Event, LinkType, Parser, Tag, TagEnd};
+use pulldown_cmark::{
+    BrokenLink, CodeBlockKind, CowStr::Borrowed, Event, LinkType, Options, Parser, Tag, TagEnd,
+};
 use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 use std::iter::repeat;
+use std::ops::Range;
 
 use crate::detect::WhitespaceDetector;
+use crate::features::FeatureCfg;
+use crate::ignore;
+use crate::mdx;
+use crate::pandoc;
+use crate::template::TemplateDelimiters;
 use crate::trace_log;
 
 const DEFAULT_CATEGORY: &str = "DEFAULT UNDEFINED CATEGORY";
+// A character from Unicode's private use area that temporarily replaces whitespace inside atomic
+// inline elements.
+const ATOMIC_PLACEHOLDER: char = '\u{e000}';
 
 #[derive(Clone, PartialEq)]
 enum CharEnv {
@@ -40,7 +51,10 @@ enum LineType<'a> {
     Other,
 }
 
-pub fn replace_spaces_in_links_by_nbsp(text: String) -> String {
+/// Replace all spaces in links by non-breaking spaces, which prevents line breaks inside links. If
+/// "max_text_width" is given, the texts of links longer than that many characters keep their
+/// spaces and can be wrapped, but the rest of those links, e.g. their targets, does not.
+pub fn replace_spaces_in_links_by_nbsp(text: String, max_text_width: Option<usize>) -> String {
     let text_no_nbsp = text
         .chars()
         .map(|ch| {
@@ -60,11 +74,37 @@ pub fn replace_spaces_in_links_by_nbsp(text: String) -> String {
 
     // Then, determine all byte positions in links. We cannot use the "_ =>" branch below because
     // ranges overlap and the link ranges will be undone by the wrapping ranges.
+    let events = Parser::new(&text_no_nbsp)
+        .into_offset_iter()
+        .collect::<Vec<_>>();
     byte_indices_in_links.extend(
-        Parser::new(&text_no_nbsp)
-            .into_offset_iter()
-            .filter_map(|(event, range)| match event {
-                Event::Start(Tag::Link { .. }) => Some(range.zip(repeat(CharEnv::LinkInRange))),
+        events
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, (event, range))| match event {
+                Event::Start(Tag::Link { link_type, .. }) => {
+                    let text_range = link_text_range(&events[idx + 1..])
+                        // For shortcut and collapsed links, the link text is also the reference
+                        // that has to match the link definition, whose spaces are always replaced.
+                        .filter(|_| is_wrappable_link_type(link_type))
+                        .filter(|text_range| {
+                            max_text_width.is_some_and(|max| {
+                                text_no_nbsp[text_range.clone()].chars().count() > max
+                            })
+                        })
+                        .unwrap_or_default();
+                    trace_log!(
+                        "keeping spaces in link text [{}, {})",
+                        text_range.start,
+                        text_range.end
+                    );
+                    Some(
+                        range
+                            .clone()
+                            .filter(move |el| !text_range.contains(el))
+                            .zip(repeat(CharEnv::LinkInRange)),
+                    )
+                }
                 _ => None,
             })
             .flatten(),
@@ -115,6 +155,155 @@ pub fn replace_spaces_in_links_by_nbsp(text: String) -> String {
         .collect::<String>()
 }
 
+/// AtomicMask makes inline HTML tags, templates and, optionally, inline code spans, JSX tags and
+/// expressions, wiki links, citations, bracketed spans, inline code chunks, as well as
+/// strikethrough text atomic. It temporarily replaces all whitespace inside them by a placeholder
+/// character that is not considered to be whitespace. Thus, each of them is treated like a single
+/// word when wrapping and end markers inside them never end a sentence. The original characters
+/// are restored afterwards, i.e. those elements are kept byte by byte, including any line breaks.
+pub struct AtomicMask(Vec<char>);
+
+impl AtomicMask {
+    pub fn mask(
+        text: String,
+        feature_cfg: &FeatureCfg,
+        templates: &TemplateDelimiters,
+    ) -> (String, Self) {
+        let code_spans = feature_cfg.atomic_code_spans;
+        if code_spans {
+            log::debug!("treating inline code spans as atomic");
+        } else {
+            log::debug!("not treating inline code spans as atomic");
+        }
+        let code_chunks = feature_cfg.quarto;
+        if code_chunks {
+            log::debug!("treating inline code chunks as atomic");
+        } else {
+            log::debug!("not treating inline code chunks as atomic");
+        }
+        let jsx_ranges = if feature_cfg.parse_cfg.mdx {
+            log::debug!("treating jsx tags and expressions as atomic");
+            mdx::find_jsx(&text)
+        } else {
+            log::debug!("not treating jsx tags and expressions as atomic");
+            vec![]
+        };
+        let pandoc_ranges = if feature_cfg.parse_cfg.pandoc {
+            log::debug!("treating citations and bracketed spans as atomic");
+            pandoc::find_citations_and_spans(&text)
+        } else {
+            log::debug!("not treating citations and bracketed spans as atomic");
+            vec![]
+        };
+        let mut opts = Options::empty();
+        if feature_cfg.parse_cfg.wiki_links {
+            log::debug!("treating wiki links and embeds as atomic");
+            opts.insert(Options::ENABLE_WIKILINKS);
+        } else {
+            log::debug!("not treating wiki links and embeds as atomic");
+        }
+        if feature_cfg.parse_cfg.strikethrough {
+            log::debug!("treating strikethrough text as atomic");
+            opts.insert(Options::ENABLE_STRIKETHROUGH);
+        } else {
+            log::debug!("not treating strikethrough text as atomic");
+        }
+        let byte_indices_to_mask = Parser::new_ext(&text, opts)
+            .into_offset_iter()
+            .filter_map(|(event, range)| match event {
+                Event::InlineHtml(..) => Some(range),
+                Event::Code(..) if code_spans => Some(range),
+                Event::Code(code) if code_chunks && is_inline_code_chunk(&code) => Some(range),
+                Event::Start(Tag::Strikethrough) => Some(range),
+                Event::Start(Tag::Link { link_type, .. } | Tag::Image { link_type, .. })
+                    if matches!(link_type, LinkType::WikiLink { .. }) =>
+                {
+                    Some(range)
+                }
+                _ => None,
+            })
+            .chain(templates.find(&text))
+            .chain(jsx_ranges)
+            .chain(pandoc_ranges)
+            .flatten()
+            .collect::<HashSet<_>>();
+        trace_log!(
+            "masking {} bytes of atomic elements",
+            byte_indices_to_mask.len()
+        );
+
+        // Any placeholder that is already in the document is masked, too. That way, restoring the
+        // original text is unambiguous.
+        let mut masked_chars = vec![];
+        let masked = text
+            .char_indices()
+            .map(|(idx, ch)| {
+                if ch == ATOMIC_PLACEHOLDER
+                    || (ch.is_whitespace() && byte_indices_to_mask.contains(&idx))
+                {
+                    masked_chars.push(ch);
+                    ATOMIC_PLACEHOLDER
+                } else {
+                    ch
+                }
+            })
+            .collect::<String>();
+
+        (masked, Self(masked_chars))
+    }
+
+    /// Check whether a word contains masked characters, i.e. whether it is part of an atomic
+    /// element.
+    pub fn is_masked(word: &str) -> bool {
+        word.contains(ATOMIC_PLACEHOLDER)
+    }
+
+    /// Restore all masked characters. This relies on the order of placeholders being unchanged,
+    /// which holds since wrapping never reorders words.
+    pub fn unmask(&self, text: String) -> String {
+        let mut masked_chars = self.0.iter();
+        text.chars()
+            .map(|ch| {
+                if ch == ATOMIC_PLACEHOLDER {
+                    *masked_chars
+                        .next()
+                        .expect("placeholders should not have been added")
+                } else {
+                    ch
+                }
+            })
+            .collect::<String>()
+    }
+}
+
+/// Check whether the content of an inline code span is code that is executed when rendering the
+/// document, e.g. `r x` in R Markdown or `{python} x` in Quarto.
+fn is_inline_code_chunk(code: &str) -> bool {
+    code.starts_with("r ")
+        || code
+            .strip_prefix('{')
+            .and_then(|el| el.split_once("} "))
+            .is_some_and(|(lang, _code)| {
+                !lang.is_empty() && lang.chars().all(|ch| ch.is_alphanumeric())
+            })
+}
+
+/// Determine the range covering the text of a link from the events following the link's start
+/// event. Links cannot be nested, which is why the next end of a link belongs to this link.
+fn link_text_range(events: &[(Event, Range<usize>)]) -> Option<Range<usize>> {
+    let inner = events
+        .iter()
+        .take_while(|(event, _range)| !matches!(event, Event::End(TagEnd::Link)))
+        .map(|(_event, range)| range);
+    let start = inner.clone().map(|el| el.start).min()?;
+    let end = inner.map(|el| el.end).max()?;
+    Some(start..end)
+}
+
+fn is_wrappable_link_type(link_type: &LinkType) -> bool {
+    matches!(link_type, LinkType::Inline | LinkType::Reference)
+}
+
 pub fn collate_link_defs_at_end(text: String, detector: &WhitespaceDetector) -> String {
     // First, determine all byte positions that the parser recognised. We ignore byte positions that
     // are part of start or end events for lists but not part of any list items. Those ranges could
@@ -342,6 +531,143 @@ fn get_url_and_name(line: &str) -> Option<(String, String)> {
     }
 }
 
+/// Normalise a link label the way CommonMark matches labels, i.e. case-insensitively and with
+/// consecutive whitespace collapsed. Non-breaking spaces count as whitespace, too, because they
+/// replace spaces in link texts when wrapping.
+fn normalize_label(label: &str) -> String {
+    label
+        .split(char::is_whitespace)
+        .filter(|el| !el.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Find all texts in square brackets that could be link labels, i.e. that contain no further
+/// brackets, together with the positions of their opening brackets.
+fn find_bracketed_labels(text: &str) -> Vec<(usize, &str)> {
+    text.match_indices('[')
+        .filter_map(|(idx, _bracket)| {
+            let rest = &text[idx + 1..];
+            let end = rest.find(['[', ']'])?;
+            rest[end..].starts_with(']').then(|| (idx, &rest[..end]))
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Remove all link definitions, i.e. `[link name]: url`, that are not referenced anywhere in the
+/// document. Like when collating them, only link definitions at the start of a line are
+/// considered. Empty lines that would double up after removing link definitions are removed, too.
+/// A link definition is kept if its label occurs in square brackets anywhere outside of fenced
+/// code blocks and code spans, even where the parser sees no reference, e.g. in lines that are
+/// indented accidentally and thus parsed as code. Lines in ignored sections are always kept.
+pub fn prune_link_defs(text: String, detector: &WhitespaceDetector) -> String {
+    let events = Parser::new(&text).into_offset_iter().collect::<Vec<_>>();
+    let code_ranges = events
+        .iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(..) | Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(..))) => {
+                Some(range.clone())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // The range of a list might include link definitions following it, that of its items does not.
+    let char_indices_recognised_by_parser = events
+        .iter()
+        .filter(|(event, _range)| {
+            !matches!(
+                event,
+                Event::Start(Tag::List(..)) | Event::End(TagEnd::List(..))
+            )
+        })
+        .flat_map(|(_event, range)| range.clone())
+        .collect::<HashSet<_>>();
+    let ignored = ignore::find_ignored_ranges(&events, text.len());
+    let is_def = |start: usize, line: &str| {
+        get_url_and_name(line).is_some() && !char_indices_recognised_by_parser.contains(&start)
+    };
+
+    let mut line_start = 0;
+    let def_starts = text
+        .split_inclusive('\n')
+        .filter_map(|line| {
+            let start = line_start;
+            line_start += line.len();
+            is_def(start, line).then_some(start)
+        })
+        .collect::<HashSet<_>>();
+    let referenced = find_bracketed_labels(&text)
+        .into_iter()
+        .filter(|(start, _label)| {
+            !def_starts.contains(start) && !code_ranges.iter().any(|el| el.contains(start))
+        })
+        .map(|(_start, label)| normalize_label(label))
+        .collect::<HashSet<_>>();
+
+    let mut line_start = 0;
+    let mut last_output_line_is_empty = true;
+    let mut pruned_after_empty_line = false;
+    let mut pruned = text
+        .split_inclusive('\n')
+        .filter(|line| {
+            let start = line_start;
+            line_start += line.len();
+            let is_empty = line.chars().all(|ch| detector.is_whitespace(&ch));
+            let is_ignored = ignored.iter().any(|el| el.contains(&start));
+
+            let unused_def = get_url_and_name(line)
+                .filter(|_| !is_ignored && is_def(start, line))
+                .filter(|(_url, name)| !referenced.contains(&normalize_label(name)));
+            if let Some((_url, name)) = unused_def {
+                log::debug!("removing unused link def: {}", name);
+                pruned_after_empty_line = last_output_line_is_empty;
+                false
+            } else if is_empty && pruned_after_empty_line && !is_ignored {
+                pruned_after_empty_line = false;
+                false
+            } else {
+                pruned_after_empty_line = false;
+                last_output_line_is_empty = is_empty;
+                true
+            }
+        })
+        .collect::<String>();
+    // Do not keep an empty line at the end of the document that only preceded pruned definitions.
+    if pruned_after_empty_line
+        && let Some(idx) = pruned.strip_suffix('\n').and_then(|el| el.rfind('\n'))
+    {
+        pruned.truncate(idx + 1);
+    }
+    pruned
+}
+
+/// Find references like `[text][label]` or `[label][]` without a matching link definition.
+/// CommonMark renders them as literal text including the brackets. Shortcut references like
+/// `[label]` are not reported because brackets are frequently used without meaning a link. The
+/// returned byte positions are sorted.
+pub fn find_undefined_references(text: &str) -> Vec<(usize, String)> {
+    let mut undefined = vec![];
+    Parser::new_with_broken_link_callback(
+        text,
+        Options::empty(),
+        Some(|link: BrokenLink| {
+            // The span of a collapsed reference does not include the trailing brackets.
+            let end = match link.link_type {
+                LinkType::Reference => Some(link.span.end),
+                LinkType::Collapsed => Some(link.span.end + "[]".len()),
+                _ => None,
+            };
+            if let Some(end) = end.filter(|el| el <= &text.len()) {
+                undefined.push((link.span.start, text[link.span.start..end].to_string()));
+            }
+            None
+        }),
+    )
+    .for_each(drop);
+    undefined
+}
+
 pub fn outsource_inline_links(
     text: String,
     collate_link_defs: &bool,
@@ -481,13 +807,142 @@ pub fn outsource_inline_links(
 mod test {
     use super::*;
 
+    #[test]
+    fn masking_and_unmasking_code_spans() {
+        let text = String::from(
+            "Some `code. with  spaces` and\n`more\ncode` but not ``x`` here \u{e000}.\n\n    code block\n",
+        );
+
+        let (masked, mask) = AtomicMask::mask(
+            text.clone(),
+            &FeatureCfg {
+                atomic_code_spans: true,
+                ..Default::default()
+            },
+            &TemplateDelimiters::default(),
+        );
+
+        let expected = "Some `code.\u{e000}with\u{e000}\u{e000}spaces` and\n`more\u{e000}code` but not ``x`` here \u{e000}.\n\n    code block\n";
+        assert_eq!(expected, masked);
+        assert_eq!(text, mask.unmask(masked));
+    }
+
+    #[test]
+    fn masking_and_unmasking_inline_html() {
+        let text = String::from(
+            "Press <kbd>Ctrl C</kbd> or <abbr title=\"e.g. This\">this</abbr> <!-- a b -->, `a b`.\n\n<div class=\"a b\">\n",
+        );
+
+        let (masked, mask) = AtomicMask::mask(
+            text.clone(),
+            &FeatureCfg::default(),
+            &TemplateDelimiters::default(),
+        );
+
+        let expected = "Press <kbd>Ctrl C</kbd> or <abbr\u{e000}title=\"e.g.\u{e000}This\">this</abbr> <!--\u{e000}a\u{e000}b\u{e000}-->, `a b`.\n\n<div class=\"a b\">\n";
+        assert_eq!(expected, masked);
+        assert_eq!(text, mask.unmask(masked));
+    }
+
+    #[test]
+    fn masking_and_unmasking_templates() -> anyhow::Result<()> {
+        let text = String::from("A {{< figure caption=\"One. Two.\" >}} or {% if a\nb %} here.\n");
+
+        let templates = "hugo markdoc".parse::<TemplateDelimiters>()?;
+        let (masked, mask) = AtomicMask::mask(text.clone(), &FeatureCfg::default(), &templates);
+
+        let expected = "A {{<\u{e000}figure\u{e000}caption=\"One.\u{e000}Two.\"\u{e000}>}} or {%\u{e000}if\u{e000}a\u{e000}b\u{e000}%} here.\n";
+        assert_eq!(expected, masked);
+        assert_eq!(text, mask.unmask(masked));
+        Ok(())
+    }
+
+    #[test]
+    fn masking_and_unmasking_jsx() {
+        let text = String::from(
+            "<Tabs\n  groupId=\"a b\">\n\nUse {props.x + 1} or <Badge text=\"A. B\" />, `{a b}`.\n",
+        );
+
+        let mut cfg = FeatureCfg::default();
+        cfg.parse_cfg.mdx = true;
+        let (masked, mask) = AtomicMask::mask(text.clone(), &cfg, &TemplateDelimiters::default());
+
+        let expected = "<Tabs\u{e000}\u{e000}\u{e000}groupId=\"a\u{e000}b\">\n\nUse {props.x\u{e000}+\u{e000}1} or <Badge\u{e000}text=\"A.\u{e000}B\"\u{e000}/>, `{a b}`.\n";
+        assert_eq!(expected, masked);
+        assert_eq!(text, mask.unmask(masked));
+    }
+
+    #[test]
+    fn masking_and_unmasking_wiki_links() {
+        let text =
+            String::from("See [[Some Page|the page. Really]] or ![[an image.png]] and [a b](c).\n");
+
+        let mut cfg = FeatureCfg::default();
+        cfg.parse_cfg.wiki_links = true;
+        let (masked, mask) = AtomicMask::mask(text.clone(), &cfg, &TemplateDelimiters::default());
+
+        let expected = "See [[Some\u{e000}Page|the\u{e000}page.\u{e000}Really]] or ![[an\u{e000}image.png]] and [a b](c).\n";
+        assert_eq!(expected, masked);
+        assert_eq!(text, mask.unmask(masked));
+    }
+
+    #[test]
+    fn masking_and_unmasking_citations_and_spans() {
+        let text = String::from("As in [see @doe, p. 3] or [small caps]{.smallcaps}, [a b].\n");
+
+        let mut cfg = FeatureCfg::default();
+        cfg.parse_cfg.pandoc = true;
+        let (masked, mask) = AtomicMask::mask(text.clone(), &cfg, &TemplateDelimiters::default());
+
+        let expected = "As in [see\u{e000}@doe,\u{e000}p.\u{e000}3] or [small\u{e000}caps]{.smallcaps}, [a b].\n";
+        assert_eq!(expected, masked);
+        assert_eq!(text, mask.unmask(masked));
+    }
+
+    #[test]
+    fn masking_and_unmasking_inline_code_chunks() {
+        let text = String::from("Got `r mean(x) + 1`, `{python} len(y)` and `not a chunk`.\n");
+
+        let cfg = FeatureCfg {
+            quarto: true,
+            ..Default::default()
+        };
+        let (masked, mask) = AtomicMask::mask(text.clone(), &cfg, &TemplateDelimiters::default());
+
+        let expected = "Got `r\u{e000}mean(x)\u{e000}+\u{e000}1`, `{python}\u{e000}len(y)` and `not a chunk`.\n";
+        assert_eq!(expected, masked);
+        assert_eq!(text, mask.unmask(masked));
+    }
+
     #[test]
     fn replacing_spaces_only_in_links() {
         let original = "Outside of link, [inside of link](http://some-url), again outside.";
         let expected =
             "Outside of link, [inside\u{a0}of\u{a0}link](http://some-url), again outside.";
 
-        let replaced = replace_spaces_in_links_by_nbsp(original.to_string());
+        let replaced = replace_spaces_in_links_by_nbsp(original.to_string(), None);
+
+        assert_eq!(replaced, expected);
+    }
+
+    #[test]
+    fn keeping_spaces_only_in_long_link_texts() {
+        let original = "\
+            [short link](http://some-url \"some title\") and \
+            [a *long* link text](http://some-url \"some title\") and \
+            [long reference link][named link] and [long shortcut link]\n\n\
+            [named link]: http://other-link\n\
+            [long shortcut link]: http://other-link\n\
+            ";
+        let expected = "\
+            [short\u{a0}link](http://some-url\u{a0}\"some\u{a0}title\") and \
+            [a *long* link text](http://some-url\u{a0}\"some\u{a0}title\") and \
+            [long reference link][named\u{a0}link] and [long\u{a0}shortcut\u{a0}link]\n\n\
+            [named\u{a0}link]: http://other-link\n\
+            [long\u{a0}shortcut\u{a0}link]: http://other-link\n\
+            ";
+
+        let replaced = replace_spaces_in_links_by_nbsp(original.to_string(), Some(10));
 
         assert_eq!(replaced, expected);
     }
@@ -497,7 +952,7 @@ mod test {
         let original = "Some initial text, [link\u{a0}with some\u{a0}nbsp](http://some-url)";
         let expected = "Some initial text, [link\u{a0}with\u{a0}some\u{a0}nbsp](http://some-url)";
 
-        let replaced = replace_spaces_in_links_by_nbsp(original.to_string());
+        let replaced = replace_spaces_in_links_by_nbsp(original.to_string(), None);
 
         assert_eq!(replaced, expected);
     }
@@ -517,7 +972,7 @@ mod test {
             [named\u{a0}link]: http://other-link\n\
             ";
 
-        let replaced = replace_spaces_in_links_by_nbsp(original.to_string());
+        let replaced = replace_spaces_in_links_by_nbsp(original.to_string(), None);
 
         assert_eq!(replaced, expected);
     }
@@ -537,7 +992,7 @@ mod test {
             [named\u{a0}link]: http://other-link\n\
             ";
 
-        let replaced = replace_spaces_in_links_by_nbsp(original.to_string());
+        let replaced = replace_spaces_in_links_by_nbsp(original.to_string(), None);
 
         assert_eq!(replaced, expected);
     }
@@ -554,7 +1009,7 @@ mod test {
             [differently\u{a0}named\u{a0}link]: http://other-link\n\
             ";
 
-        let replaced = replace_spaces_in_links_by_nbsp(original.to_string());
+        let replaced = replace_spaces_in_links_by_nbsp(original.to_string(), None);
 
         assert_eq!(replaced, original);
     }
@@ -574,7 +1029,7 @@ mod test {
             [named\u{a0}link]: http://other-link\n\
             ";
 
-        let replaced = replace_spaces_in_links_by_nbsp(original.to_string());
+        let replaced = replace_spaces_in_links_by_nbsp(original.to_string(), None);
 
         assert_eq!(replaced, expected);
     }
@@ -781,6 +1236,119 @@ mod test {
         assert_eq!(collated, expected);
     }
 
+    #[test]
+    fn pruning_unused_link_defs() {
+        let text = "\
+See [used] and [Some  Ref][] and [text][img].
+
+[unused]: https://a.com
+[used]: https://b.com
+
+[some ref]: https://c.com
+[img]: https://d.com \"title\"
+[other unused]: https://e.com
+
+- [list item]: https://f.com
+
+[after list]: https://g.com
+";
+        let expected = "\
+See [used] and [Some  Ref][] and [text][img].
+
+[used]: https://b.com
+
+[some ref]: https://c.com
+[img]: https://d.com \"title\"
+
+- [list item]: https://f.com
+";
+        let detector = WhitespaceDetector::new(false);
+        assert_eq!(expected, prune_link_defs(text.to_string(), &detector));
+    }
+
+    #[test]
+    fn keeping_link_defs_referenced_outside_of_fenced_code() {
+        let text = "\
+Some text
+         with [![Badge Name]][ci] in an indented line.
+
+    [indented]
+
+```
+[fenced]
+```
+
+Some `[span]` and [no\u{a0}break] here.
+
+[badge name]: https://a.com/badge.svg
+[ci]: https://b.com
+[indented]: https://c.com
+[fenced]: https://d.com
+[span]: https://e.com
+[no break]: https://f.com
+";
+        let expected = "\
+Some text
+         with [![Badge Name]][ci] in an indented line.
+
+    [indented]
+
+```
+[fenced]
+```
+
+Some `[span]` and [no\u{a0}break] here.
+
+[badge name]: https://a.com/badge.svg
+[ci]: https://b.com
+[indented]: https://c.com
+[no break]: https://f.com
+";
+        let detector = WhitespaceDetector::new(false);
+        assert_eq!(expected, prune_link_defs(text.to_string(), &detector));
+    }
+
+    #[test]
+    fn keeping_link_defs_in_ignored_sections() {
+        let text = "\
+Text.
+
+<!-- mdslw-ignore-start -->
+[keepme]: https://a.com
+<!-- mdslw-ignore-end -->
+
+[unused]: https://b.com
+
+<!-- mdslw-ignore-start -->
+
+[keepme too]: https://c.com
+";
+        let expected = "\
+Text.
+
+<!-- mdslw-ignore-start -->
+[keepme]: https://a.com
+<!-- mdslw-ignore-end -->
+
+<!-- mdslw-ignore-start -->
+
+[keepme too]: https://c.com
+";
+        let detector = WhitespaceDetector::new(false);
+        assert_eq!(expected, prune_link_defs(text.to_string(), &detector));
+    }
+
+    #[test]
+    fn finding_undefined_references() {
+        let text = "[a][missing] and [b][defined] and [missing][], but not [shortcut] or [c](url).\n\n\
+                    [defined]: https://a.com\n";
+        let expected = vec![
+            (0, "[a][missing]".to_string()),
+            (34, "[missing][]".to_string()),
+        ];
+        assert_eq!(expected, find_undefined_references(text));
+    }
+
     #[test]
     fn outsourcing_inline_links_expecting_link_def_collation() {
         let original = "\
@@ -0,0 +1,218 @@
+/* An opinionated line wrapper for markdown files.
+Copyright (C) 2023  Torsten Long
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::detect::BreakDetector;
+use crate::features::FeatureCfg;
+use crate::parse::parse_markdown;
+use crate::replace::AtomicMask;
+use crate::template::TemplateDelimiters;
+use crate::trace_log;
+
+#[derive(Debug, Default, PartialEq)]
+struct Occurrences {
+    // How often a word was followed by a word starting with a lowercase letter or a number.
+    continued: usize,
+    // How often a word occurred at a position where it would cause a line break.
+    total: usize,
+}
+
+/// Collect words that end in an end marker but that are usually not at the end of a sentence
+/// because they are usually followed by a lowercase word or a number. Such words are good
+/// candidates for suppressions.
+#[derive(Debug, Default)]
+pub struct SuppressionCandidates(HashMap<String, Occurrences>);
+
+impl SuppressionCandidates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan all text in the document that would be wrapped. Words that are already suppressed are
+    /// not considered. Atomic elements are masked like when formatting and words in them are
+    /// skipped.
+    pub fn add_document(
+        &mut self,
+        text: &str,
+        detector: &BreakDetector,
+        feature_cfg: &FeatureCfg,
+        templates: &TemplateDelimiters,
+    ) {
+        let (text, _mask) = AtomicMask::mask(text.to_string(), feature_cfg, templates);
+        for range in parse_markdown(&text, &feature_cfg.parse_cfg, templates) {
+            let words = detector
+                .whitespace
+                .split_whitespace(&text[range])
+                .collect::<Vec<_>>();
+
+            for pair in words.windows(2) {
+                if AtomicMask::is_masked(pair[0]) {
+                    continue;
+                }
+                // Ignore characters such as opening parentheses or quotes at the start of a word.
+                let word = pair[0].trim_start_matches(|ch: char| !ch.is_alphanumeric());
+                let chars = word.chars().collect::<Vec<_>>();
+                let is_candidate = chars.iter().any(|ch| ch.is_alphabetic())
+                    && chars.last().is_some_and(|last| {
+                        detector.is_breaking_marker(last, Some(&' '))
+                            && !detector.ends_with_keep_word(&chars, &(chars.len() - 1))
+                    });
+                if !is_candidate {
+                    continue;
+                }
+
+                let is_continued = pair[1]
+                    .chars()
+                    .next()
+                    .is_some_and(|ch| ch.is_lowercase() || ch.is_numeric());
+                trace_log!("found possible suppression {} before {}", word, pair[1]);
+
+                let occurrences = self.0.entry(word.to_string()).or_default();
+                occurrences.total += 1;
+                if is_continued {
+                    occurrences.continued += 1;
+                }
+            }
+        }
+    }
+
+    /// Retrieve all words that are followed by a lowercase word or a number in the majority of
+    /// cases, ranked by how often that happened.
+    fn ranked(&self) -> Vec<(&str, &Occurrences)> {
+        let mut ranked = self
+            .0
+            .iter()
+            .filter(|(_word, occ)| 2 * occ.continued > occ.total)
+            .map(|(word, occ)| (word.as_str(), occ))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|(w1, o1), (w2, o2)| {
+            o2.continued
+                .cmp(&o1.continued)
+                .then(o2.total.cmp(&o1.total))
+                .then(w1.cmp(w2))
+        });
+        ranked
+    }
+
+    /// Format the candidates as a config file. Each candidate is listed in a comment with its
+    /// statistics to simplify pruning the list by hand.
+    pub fn to_config(&self) -> String {
+        let ranked = self.ranked();
+        log::debug!("found {} candidate suppressions", ranked.len());
+
+        // The "write!" calls should never fail since we write to a String that we create here.
+        let mut result = String::from(
+            "# Candidate suppressions ranked by the number of times they were followed by a\n\
+             # lowercase word or a number, out of all times they would have caused a line break.\n",
+        );
+        for (word, occ) in &ranked {
+            writeln!(result, "# {} => {}/{}", word, occ.continued, occ.total)
+                .expect("building suggestion output");
+        }
+        let words = ranked
+            .iter()
+            .map(|(word, _occ)| word.replace('\\', "\\\\").replace('"', "\\\""))
+            .collect::<Vec<_>>();
+        writeln!(result, "suppressions = \"{}\"", words.join(" "))
+            .expect("building suggestion output");
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::detect::BreakCfg;
+
+    const CFG_FOR_TESTS: &BreakCfg = &BreakCfg {
+        keep_linebreaks: false,
+    };
+
+    #[test]
+    fn finding_and_ranking_candidates() {
+        let text = r#"
+# Heading with approx. 3 words.
+
+See fig. 1 and (fig. 2) as well as Fig. 3.
+This is approx. the end. The end is near, cf. the docs.
+Take approx. 5 minutes. Then stop.
+
+```
+Code such as fig. 1 is ignored.
+```
+"#;
+        let detector = BreakDetector::new("cf.", "", true, ".", CFG_FOR_TESTS);
+        let mut candidates = SuppressionCandidates::new();
+        candidates.add_document(
+            text,
+            &detector,
+            &FeatureCfg::default(),
+            &TemplateDelimiters::default(),
+        );
+
+        let expected = r#"# Candidate suppressions ranked by the number of times they were followed by a
+# lowercase word or a number, out of all times they would have caused a line break.
+# approx. => 2/2
+# fig. => 2/2
+# Fig. => 1/1
+suppressions = "approx. fig. Fig."
+"#;
+        assert_eq!(expected, candidates.to_config());
+    }
+
+    #[test]
+    fn rarely_continued_words_are_no_candidates() {
+        let text = "It ends. It ends. But it ends. here.\n";
+        let detector = BreakDetector::new("", "", false, ".", CFG_FOR_TESTS);
+        let mut candidates = SuppressionCandidates::new();
+        candidates.add_document(
+            text,
+            &detector,
+            &FeatureCfg::default(),
+            &TemplateDelimiters::default(),
+        );
+
+        assert!(candidates.ranked().is_empty());
+        assert_eq!(
+            Some(&Occurrences {
+                continued: 1,
+                total: 3
+            }),
+            candidates.0.get("ends.")
+        );
+    }
+
+    #[test]
+    fn code_spans_are_no_candidates() {
+        let text = "Run `ls -l. then` or <abbr title=\"approx. 5\">ca.</abbr> as in fig. 1 here.\n";
+        let detector = BreakDetector::new("", "", false, ".", CFG_FOR_TESTS);
+        let mut candidates = SuppressionCandidates::new();
+        candidates.add_document(
+            text,
+            &detector,
+            &FeatureCfg {
+                atomic_code_spans: true,
+                ..Default::default()
+            },
+            &TemplateDelimiters::default(),
+        );
+
+        assert_eq!(vec!["fig."], candidates.0.keys().collect::<Vec<_>>());
+    }
+}
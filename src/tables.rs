@@ -0,0 +1,225 @@
+/* An opinionated line wrapper for markdown files.
+Copyright (C) 2023  Torsten Long
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use pulldown_cmark::{Alignment, Event, Options, Parser, Tag};
+
+use crate::ignore;
+use crate::trace_log;
+use crate::wrap::text_width;
+
+// The delimiter row needs at least three characters per column, e.g. "---" or ":-:".
+const MIN_COLUMN_WIDTH: usize = 3;
+
+/// Align all pipe tables in the document. Cells are padded such that columns line up and the
+/// delimiter row reflects the alignment of each column. Cells are never broken across lines. Tables
+/// whose rows contain more cells than their header row or that are inside other containers than
+/// lists, e.g. block quotes, are kept as they are. So are tables in ignored sections.
+pub fn align_tables(text: String) -> String {
+    let events = Parser::new_ext(&text, Options::ENABLE_TABLES)
+        .into_offset_iter()
+        .collect::<Vec<_>>();
+    let ignored = ignore::find_ignored_ranges(&events, text.len());
+    let tables = events
+        .into_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::Table(alignments)) => Some((range, alignments)),
+            _ => None,
+        })
+        .filter(|(range, _alignments)| !ignored.iter().any(|el| el.contains(&range.start)))
+        .collect::<Vec<_>>();
+
+    // Replace tables from back to front. That way, the ranges of the remaining ones stay valid.
+    let mut result = text.clone();
+    for (range, alignments) in tables.into_iter().rev() {
+        // Tables might be indented, e.g. in lists. Align them including their indent.
+        let start = text[..range.start].rfind('\n').map_or(0, |el| el + 1);
+        let table = text[start..range.end].trim_end_matches('\n');
+        let is_indented_only = text[start..range.start].trim().is_empty();
+        if let Some(aligned) = align_table(table, &alignments).filter(|_| is_indented_only) {
+            trace_log!("aligning table:\n{}", table);
+            result.replace_range(start..start + table.len(), &aligned);
+        } else {
+            trace_log!("not aligning table:\n{}", table);
+        }
+    }
+    result
+}
+
+fn align_table(table: &str, alignments: &[Alignment]) -> Option<String> {
+    let lines = table.lines().collect::<Vec<_>>();
+    let indent = lines.first()?.len() - lines.first()?.trim_start_matches(' ').len();
+    if !lines
+        .iter()
+        .all(|line| line.len() > indent && line[..indent].trim().is_empty())
+    {
+        return None;
+    }
+
+    let mut rows = lines
+        .iter()
+        .map(|line| split_row(line.trim()))
+        .collect::<Vec<_>>();
+    let num_columns = alignments.len();
+    if rows.len() < 2 || rows.iter().any(|row| row.len() > num_columns) {
+        return None;
+    }
+    // Rows with fewer cells are filled up with empty ones, which does not change the table.
+    for row in rows.iter_mut() {
+        row.resize(num_columns, String::new());
+    }
+
+    let widths = (0..num_columns)
+        .map(|col| {
+            rows.iter()
+                .enumerate()
+                // Skip the delimiter row, which is generated anew.
+                .filter(|(idx, _row)| *idx != 1)
+                .map(|(_idx, row)| text_width(&row[col]))
+                .chain([MIN_COLUMN_WIDTH])
+                .max()
+                .unwrap_or(MIN_COLUMN_WIDTH)
+        })
+        .collect::<Vec<_>>();
+
+    let prefix = " ".repeat(indent);
+    let aligned = rows
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let cells = row
+                .iter()
+                .zip(alignments.iter().zip(widths.iter()))
+                .map(|(cell, (alignment, width))| {
+                    if idx == 1 {
+                        delimiter_cell(alignment, *width)
+                    } else {
+                        pad_cell(cell, alignment, *width)
+                    }
+                })
+                .collect::<Vec<_>>();
+            format!("{}| {} |", prefix, cells.join(" | "))
+        })
+        .collect::<Vec<_>>();
+
+    Some(aligned.join("\n"))
+}
+
+/// Split a row into its cells at every pipe that is not escaped. Leading and trailing pipes are
+/// optional.
+fn split_row(line: &str) -> Vec<String> {
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut escaped = false;
+    for ch in line.chars() {
+        if ch == '|' && !escaped {
+            cells.push(cell.trim().to_string());
+            cell.clear();
+        } else {
+            cell.push(ch);
+        }
+        escaped = ch == '\\' && !escaped;
+    }
+    cells.push(cell.trim().to_string());
+
+    // Remove the empty cells caused by leading and trailing pipes.
+    if line.starts_with('|') {
+        cells.remove(0);
+    }
+    if cells.len() > 1 && cells.last().is_some_and(|el| el.is_empty()) {
+        cells.pop();
+    }
+    cells
+}
+
+fn pad_cell(cell: &str, alignment: &Alignment, width: usize) -> String {
+    let padding = width.saturating_sub(text_width(cell));
+    let (left, right) = match alignment {
+        Alignment::None | Alignment::Left => (0, padding),
+        Alignment::Center => (padding / 2, padding - padding / 2),
+        Alignment::Right => (padding, 0),
+    };
+    format!("{}{}{}", " ".repeat(left), cell, " ".repeat(right))
+}
+
+fn delimiter_cell(alignment: &Alignment, width: usize) -> String {
+    match alignment {
+        Alignment::None => "-".repeat(width),
+        Alignment::Left => format!(":{}", "-".repeat(width - 1)),
+        Alignment::Center => format!(":{}:", "-".repeat(width - 2)),
+        Alignment::Right => format!("{}:", "-".repeat(width - 1)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aligning_tables() {
+        let text = "\
+Some text.
+
+|a|Centred column|right|
+|:-|:-:|--:|
+|longer cell|x|1|
+| \\| escaped | `code` |
+no pipes|b|c
+
+- List item.
+
+    Name | Value
+    ---|---
+    é | 12
+";
+        let expected = "\
+Some text.
+
+| a           | Centred column | right |
+| :---------- | :------------: | ----: |
+| longer cell |       x        |     1 |
+| \\| escaped  |     `code`     |       |
+| no pipes    |       b        |     c |
+
+- List item.
+
+    | Name | Value |
+    | ---- | ----- |
+    | é    | 12    |
+";
+        assert_eq!(expected, align_tables(text.to_string()));
+    }
+
+    #[test]
+    fn keeping_tables_with_extra_cells_and_in_block_quotes() {
+        let text = "\
+| a | b |
+|---|---|
+| 1 | 2 | 3 |
+
+> | a | b |
+> |---|---|
+> | 1 | 2 |
+";
+        assert_eq!(text, align_tables(text.to_string()));
+    }
+
+    #[test]
+    fn keeping_tables_in_ignored_sections() {
+        let text = "<!-- mdslw-ignore-start -->\n|a|b|\n|-|:-|\n|c|d|\n<!-- mdslw-ignore-end -->\n";
+        assert_eq!(text, align_tables(text.to_string()));
+    }
+}
@@ -0,0 +1,267 @@
+/* An opinionated line wrapper for markdown files.
+Copyright (C) 2023  Torsten Long
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anyhow::{Error, Result};
+
+use crate::parse::CharRange;
+use crate::trace_log;
+
+const PAIR_SEPARATOR: char = ',';
+
+/// Delimiter pairs of the template languages used by common static site generators.
+fn preset(name: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    match name {
+        "hugo" => Some(&[("{{<", ">}}"), ("{{%", "%}}"), ("{{", "}}")]),
+        "jekyll" | "liquid" => Some(&[("{{", "}}"), ("{%", "%}")]),
+        "jinja" => Some(&[("{{", "}}"), ("{%", "%}"), ("{#", "#}")]),
+        "markdoc" => Some(&[("{%", "%}")]),
+        _ => None,
+    }
+}
+
+/// TemplateDelimiters describes pairs of delimiters that enclose template code such as shortcodes
+/// or tags, e.g. `{{< figure src="a.png" >}}`.
+#[derive(Debug, Default, PartialEq)]
+pub struct TemplateDelimiters(Vec<(String, String)>);
+
+impl std::str::FromStr for TemplateDelimiters {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut pairs = vec![];
+        let mut errors = vec![];
+
+        // Each entry is either the name of a preset or a custom pair like "<%,%>". Like features,
+        // presets may also be separated by commas, e.g. "hugo,jekyll".
+        for entry in s.split_whitespace() {
+            let presets = entry
+                .split(PAIR_SEPARATOR)
+                .map(preset)
+                .collect::<Option<Vec<_>>>();
+            if let Some(presets) = presets {
+                pairs.extend(
+                    presets
+                        .into_iter()
+                        .flatten()
+                        .map(|(open, close)| (open.to_string(), close.to_string())),
+                );
+            } else {
+                match entry.split_once(PAIR_SEPARATOR) {
+                    Some((open, close)) if !open.is_empty() && !close.is_empty() => {
+                        pairs.push((open.to_string(), close.to_string()))
+                    }
+                    _ => errors.push(entry),
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            let mut pairs = pairs.into_iter().fold(vec![], |mut unique, pair| {
+                if !unique.contains(&pair) {
+                    unique.push(pair);
+                }
+                unique
+            });
+            // Check longer opening delimiters first. That way, "{{<" takes precedence over "{{".
+            pairs.sort_by_key(|(open, _close)| std::cmp::Reverse(open.len()));
+            log::debug!("using template delimiters: {:?}", pairs);
+            Ok(Self(pairs))
+        } else {
+            Err(Error::msg(format!(
+                "unknown template delimiters: {}",
+                errors.join(", ")
+            )))
+        }
+    }
+}
+
+impl TemplateDelimiters {
+    /// Find all byte ranges enclosed in delimiters, including the delimiters themselves. Templates
+    /// cannot be nested and never span paragraphs, i.e. an opening delimiter without a closing one
+    /// before the next empty line is ignored. The ranges are sorted and do not overlap.
+    pub fn find(&self, text: &str) -> Vec<CharRange> {
+        let mut ranges = vec![];
+        let mut pos = 0;
+
+        while pos < text.len() {
+            // Find the opening delimiter that occurs first. If several occur at the same position,
+            // the longest one is used since they are sorted by length.
+            let first_open = self
+                .0
+                .iter()
+                .filter_map(|(open, close)| {
+                    text[pos..]
+                        .find(open.as_str())
+                        .map(|idx| (pos + idx, open, close))
+                })
+                .min_by_key(|(start, _open, _close)| *start);
+
+            let Some((start, open, close)) = first_open else {
+                break;
+            };
+            let content_start = start + open.len();
+            let content_end = content_start + find_paragraph_end(&text[content_start..]);
+            if let Some(idx) = text[content_start..content_end].find(close.as_str()) {
+                let end = content_start + idx + close.len();
+                trace_log!("found template: {}", &text[start..end]);
+                ranges.push(CharRange { start, end });
+                pos = end;
+            } else {
+                pos = content_start;
+            }
+        }
+
+        ranges
+    }
+
+    /// Find all lines that consist of nothing but template code, e.g. block-level shortcodes like
+    /// `{{< tabs >}}`. The ranges include the line break preceding such a line, which keeps it from
+    /// being joined with the text before it.
+    pub fn find_lines(&self, text: &str) -> Vec<CharRange> {
+        let templates = self.find(text);
+        let mut lines = vec![];
+        let mut idx = 0;
+
+        while idx < templates.len() {
+            let start = templates[idx].start;
+            let line_start = text[..start].rfind('\n').map_or(0, |el| el + 1);
+            // Several templates might be on the same line, separated by whitespace only.
+            let mut end = templates[idx].end;
+            idx += 1;
+            while idx < templates.len()
+                && text[end..templates[idx].start]
+                    .chars()
+                    .all(|ch| ch.is_whitespace() && ch != '\n')
+            {
+                end = templates[idx].end;
+                idx += 1;
+            }
+            let line_end = text[end..]
+                .find('\n')
+                .map(|el| end + el + 1)
+                .unwrap_or(text.len());
+
+            if text[line_start..start].trim().is_empty() && text[end..line_end].trim().is_empty() {
+                trace_log!("found template line: {}", &text[start..end]);
+                lines.push(CharRange {
+                    start: line_start.saturating_sub(1),
+                    end: line_end,
+                });
+            }
+        }
+
+        lines
+    }
+}
+
+/// Find the end of the paragraph at the start of "text", i.e. the position of the first empty line.
+fn find_paragraph_end(text: &str) -> usize {
+    let mut last_was_linebreak = false;
+    for (idx, ch) in text.char_indices() {
+        if ch == '\n' && last_was_linebreak {
+            return idx;
+        }
+        if ch != ' ' && ch != '\t' {
+            last_was_linebreak = ch == '\n';
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing_presets_and_custom_delimiters() -> Result<()> {
+        let parsed = "markdoc  <%,%> jinja".parse::<TemplateDelimiters>()?;
+
+        let expected = TemplateDelimiters(
+            [("{%", "%}"), ("<%", "%>"), ("{{", "}}"), ("{#", "#}")]
+                .into_iter()
+                .map(|(open, close)| (open.to_string(), close.to_string()))
+                .collect(),
+        );
+        assert_eq!(expected, parsed);
+
+        assert_eq!(
+            "hugo jekyll".parse::<TemplateDelimiters>()?,
+            "hugo,jekyll".parse::<TemplateDelimiters>()?
+        );
+
+        assert!("unknown".parse::<TemplateDelimiters>().is_err());
+        assert!("{{,".parse::<TemplateDelimiters>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn finding_templates() -> Result<()> {
+        let text = "Some {{< figure caption=\"One. Two.\" >}} and {{ page.title }}, then\n\
+                    {% include x.html\n   param=\"a b\" %} but {{ unclosed.";
+        let delimiters = "hugo jekyll".parse::<TemplateDelimiters>()?;
+
+        let found = delimiters
+            .find(text)
+            .into_iter()
+            .map(|el| &text[el])
+            .collect::<Vec<_>>();
+
+        let expected = vec![
+            "{{< figure caption=\"One. Two.\" >}}",
+            "{{ page.title }}",
+            "{% include x.html\n   param=\"a b\" %}",
+        ];
+        assert_eq!(expected, found);
+        Ok(())
+    }
+
+    #[test]
+    fn ignoring_unclosed_templates() -> Result<()> {
+        let text = "Some {{ unclosed text.\n\nMore text. }} and {{ closed\n  text }}.\n\n{{";
+        let delimiters = "hugo".parse::<TemplateDelimiters>()?;
+
+        let found = delimiters
+            .find(text)
+            .into_iter()
+            .map(|el| &text[el])
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["{{ closed\n  text }}"], found);
+        Ok(())
+    }
+
+    #[test]
+    fn finding_lines_with_only_templates() -> Result<()> {
+        let text =
+            "{{< tabs >}}\n  {{< tab \"A\" >}} {{% note %}}\nText {{< x >}}\n{{< a\n  b >}}\n";
+        let delimiters = "hugo".parse::<TemplateDelimiters>()?;
+
+        let found = delimiters
+            .find_lines(text)
+            .into_iter()
+            .map(|el| &text[el])
+            .collect::<Vec<_>>();
+
+        let expected = vec![
+            "{{< tabs >}}\n",
+            "\n  {{< tab \"A\" >}} {{% note %}}\n",
+            "\n{{< a\n  b >}}\n",
+        ];
+        assert_eq!(expected, found);
+        Ok(())
+    }
+}
@@ -15,7 +15,8 @@ You should have received a copy of the GNU General Public License
 along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::detect::{BreakDetector, WhitespaceDetector};
+use crate::cfg::WrapMode;
+use crate::detect::{BreakDetector, WhitespaceDetector, starts_block_element};
 use crate::indent::build_indent;
 use crate::linebreak::insert_linebreaks_after_sentence_ends;
 use crate::ranges::{TextRange, WrapType};
@@ -24,6 +25,7 @@ use crate::trace_log;
 pub fn add_linebreaks_and_wrap(
     ranges: Vec<TextRange>,
     max_width: &Option<usize>,
+    wrap_mode: &WrapMode,
     detector: &BreakDetector,
     text: &str,
 ) -> String {
@@ -52,6 +54,7 @@ pub fn add_linebreaks_and_wrap(
                         max_width,
                         &indent,
                         &detector.whitespace,
+                        wrap_mode,
                     )
                 })
                 .collect::<Vec<_>>()
@@ -84,54 +87,206 @@ fn wrap_long_line_and_collapse_inline_whitespace(
     max_width: &Option<usize>,
     indent: &str,
     detector: &WhitespaceDetector,
+    wrap_mode: &WrapMode,
 ) -> Vec<String> {
     let ends_w_2_spaces = sentence.ends_with("  ");
-    let mut lines = vec![];
     let mut words = detector
         .split_whitespace(sentence)
-        .filter(|el| !el.is_empty());
-    let (mut line, first_indent_len) = if let Some(first_word) = words.next() {
-        // The first sentence is already properly indented. Every other sentence has to be
-        // indented manually.
-        if sentence_idx == 0 {
-            (String::from(first_word), indent.chars().count())
-        } else {
-            (format!("{}{}", indent, first_word), 0)
-        }
-    } else {
-        (String::new(), 0)
+        .filter(|el| !el.is_empty())
+        .map(String::from)
+        .collect::<Vec<_>>();
+    // Treat the two trailing spaces as part of the last word. That way, they are taken into
+    // account when determining the line width.
+    match words.last_mut() {
+        Some(last) if ends_w_2_spaces => last.push_str("  "),
+        Some(_) => {}
+        None if ends_w_2_spaces => return vec![String::from("  ")],
+        None => return vec![String::new()],
+    }
+
+    let widths = words.iter().map(|el| text_width(el)).collect::<Vec<_>>();
+    // A line must not start with a word that would turn it into a block-level element, e.g. a
+    // list item or a heading. The first word is already at the start of a line anyway.
+    let can_start_line = words
+        .iter()
+        .enumerate()
+        .map(|(idx, el)| idx == 0 || !starts_block_element(el))
+        .collect::<Vec<_>>();
+    let line_width = LineWidth {
+        widths: &widths,
+        indent: text_width(indent),
+        can_start_line: &can_start_line,
     };
-    let mut line_len = line.chars().count() + first_indent_len;
     let width = max_width.unwrap_or(0);
-    let words = words.collect::<Vec<_>>();
-    let num_words = words.len();
-    if !words.is_empty() {
-        for (idx, word) in words.into_iter().enumerate() {
-            let append_2_spaces = idx == num_words - 1 && ends_w_2_spaces;
-            let additional_spaces = if append_2_spaces { 2 } else { 0 };
-            let chars = word.chars().count();
-            if width == 0 || line_len + 1 + chars + additional_spaces <= width {
-                line.push(' ');
-                line.push_str(word);
-                if append_2_spaces {
-                    line.push_str("  ");
-                }
-                line_len += chars + 1 + additional_spaces;
+    let line_starts = if width == 0 || line_width.of(0, words.len()) <= width {
+        vec![0]
+    } else {
+        match wrap_mode {
+            WrapMode::Greedy => greedy_line_starts(&line_width, width),
+            WrapMode::Sembr => sembr_line_starts(&words, &line_width, width),
+            WrapMode::Optimal => optimal_line_starts(&line_width, width, true)
+                // Widow control cannot always be satisfied, e.g. if the last word is very long.
+                .or_else(|| optimal_line_starts(&line_width, width, false))
+                .unwrap_or_else(|| greedy_line_starts(&line_width, width)),
+        }
+    };
+
+    // The first sentence is already properly indented. Every other sentence has to be indented
+    // manually.
+    line_starts
+        .iter()
+        .zip(line_starts.iter().skip(1).chain([&words.len()]))
+        .enumerate()
+        .map(|(idx, (start, end))| {
+            let ind = if idx == 0 && sentence_idx == 0 {
+                ""
             } else {
-                lines.push(line);
-                line = String::from(indent);
-                line.push_str(word);
-                if append_2_spaces {
-                    line.push_str("  ");
-                }
-                line_len = line.chars().count();
-            }
+                indent
+            };
+            format!("{}{}", ind, words[*start..*end].join(" "))
+        })
+        .collect::<Vec<_>>()
+}
+
+/// The width of some text when displayed, which is the number of characters it consists of.
+pub fn text_width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Helper to determine the width of a line made up of some words, including the indent, and where
+/// a line may end.
+struct LineWidth<'a> {
+    widths: &'a [usize],
+    indent: usize,
+    can_start_line: &'a [bool],
+}
+
+impl LineWidth<'_> {
+    /// The width of a line containing the words in [start, end), which are separated by spaces.
+    fn of(&self, start: usize, end: usize) -> usize {
+        let num_spaces = end.saturating_sub(start + 1);
+        self.indent + self.widths[start..end].iter().sum::<usize>() + num_spaces
+    }
+
+    /// Whether a line may end before the word at "end", i.e. whether the next line may start with
+    /// that word.
+    fn is_valid_end(&self, end: usize) -> bool {
+        self.can_start_line.get(end).copied().unwrap_or(true)
+    }
+
+    /// The end of the longest line starting at "start" that still fits into "width". A line always
+    /// contains at least one word, even if that exceeds "width". If the line cannot end at any
+    /// position that fits, it is extended to the next position at which it can end.
+    fn fitting_end(&self, start: usize, width: usize) -> usize {
+        let num_words = self.widths.len();
+        (start + 1..=num_words)
+            .take_while(|end| *end == start + 1 || self.of(start, *end) <= width)
+            .filter(|end| self.is_valid_end(*end))
+            .last()
+            .or_else(|| (start + 1..=num_words).find(|end| self.is_valid_end(*end)))
+            .unwrap_or(num_words)
+    }
+}
+
+/// Determine the indices of the words that start a line by putting as many words as possible on
+/// each line.
+fn greedy_line_starts(line_width: &LineWidth, width: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut start = 0;
+    while start < line_width.widths.len() {
+        start = line_width.fitting_end(start, width);
+        if start < line_width.widths.len() {
+            starts.push(start);
+        }
+    }
+    starts
+}
+
+// Words after which a line break is preferred when using semantic line breaks.
+const SEMBR_BREAK_AFTER: [char; 3] = [',', ';', '—'];
+// Conjunctions before which a line break is preferred when using semantic line breaks.
+const SEMBR_BREAK_BEFORE: [&str; 12] = [
+    "and", "but", "or", "nor", "yet", "so", "because", "although", "though", "whereas", "while",
+    "unless",
+];
+
+/// Determine whether the boundary between the words at "end-1" and "end" is a clause boundary.
+fn is_clause_boundary(words: &[String], end: usize) -> bool {
+    words[end - 1].ends_with(SEMBR_BREAK_AFTER)
+        || words
+            .get(end)
+            .is_some_and(|el| SEMBR_BREAK_BEFORE.contains(&el.to_lowercase().as_str()))
+}
+
+/// Determine the indices of the words that start a line by breaking at the last clause boundary
+/// that still fits on a line. If there is no such boundary, as many words as possible are put on
+/// the line. See https://sembr.org for the semantic line breaks specification.
+fn sembr_line_starts(words: &[String], line_width: &LineWidth, width: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut start = 0;
+    while line_width.of(start, words.len()) > width {
+        let fitting_end = line_width.fitting_end(start, width);
+        if fitting_end == words.len() {
+            break;
         }
-    } else if ends_w_2_spaces {
-        line.push_str("  ");
+        start = (start + 1..=fitting_end)
+            .rev()
+            .find(|end| line_width.is_valid_end(*end) && is_clause_boundary(words, *end))
+            .unwrap_or(fitting_end);
+        starts.push(start);
     }
-    lines.push(line);
-    lines
+    starts
+}
+
+// Factor by which the squared excess width of a line that is too long is weighted. That way, any
+// line layout with fewer or shorter overlong lines is preferred.
+const OVERLONG_PENALTY: usize = 1_000;
+
+/// Determine the indices of the words that start a line by minimising the raggedness of all
+/// lines, similar to the Knuth-Plass algorithm. The cost of a line is the square of the number of
+/// unused characters, apart from the last line, which is free. Lines that are too long are
+/// penalised heavily unless they consist of a single word, which cannot be broken anyway. With
+/// widow control, the last line must not contain just a single word. Returns None if there is no
+/// such line layout.
+fn optimal_line_starts(
+    line_width: &LineWidth,
+    width: usize,
+    widow_control: bool,
+) -> Option<Vec<usize>> {
+    let num_words = line_width.widths.len();
+    // The minimum cost of laying out all words starting at the index and the start of the next
+    // line for that minimum.
+    let mut min_costs: Vec<Option<(usize, usize)>> = vec![None; num_words + 1];
+    min_costs[num_words] = Some((0, num_words));
+
+    for start in (0..num_words).rev() {
+        min_costs[start] = (start + 1..=line_width.fitting_end(start, width))
+            .filter(|end| line_width.is_valid_end(*end))
+            .filter(|end| !(widow_control && start > 0 && *end == num_words && end - start == 1))
+            .filter_map(|end| {
+                let rest = min_costs[end]?.0;
+                let line = line_width.of(start, end);
+                let cost = if line > width && end - start > 1 {
+                    (line - width).pow(2) * OVERLONG_PENALTY
+                } else if end == num_words {
+                    0
+                } else {
+                    width.saturating_sub(line).pow(2)
+                };
+                Some((cost + rest, end))
+            })
+            .min();
+    }
+
+    let mut starts = vec![0];
+    let mut start = 0;
+    while start < num_words {
+        start = min_costs[start]?.1;
+        if start < num_words {
+            starts.push(start);
+        }
+    }
+    Some(starts)
 }
 
 #[cfg(test)]
@@ -157,6 +312,7 @@ mod test {
             &Some(max_width),
             indent,
             &WhitespaceDetector::default(),
+            &WrapMode::Greedy,
         );
 
         // No indent for the start of the sentence due to the sentence_idx.
@@ -187,6 +343,7 @@ mod test {
             &Some(max_width),
             indent,
             &WhitespaceDetector::default(),
+            &WrapMode::Greedy,
         );
 
         // No indent for the start of the sentence due to the sentence_idx.
@@ -217,6 +374,7 @@ mod test {
             &Some(max_width),
             indent,
             &WhitespaceDetector::default(),
+            &WrapMode::Greedy,
         );
 
         // No indent for the start of the sentence due to the sentence_idx.
@@ -237,6 +395,7 @@ mod test {
             &None,
             indent,
             &WhitespaceDetector::default(),
+            &WrapMode::Greedy,
         );
 
         // No indent for the start of the sentence due to the sentence_idx.
@@ -258,6 +417,7 @@ mod test {
             &Some(max_width),
             indent,
             &WhitespaceDetector::default(),
+            &WrapMode::Greedy,
         );
 
         // Note the indent for the start of the sentence due to the sentence_idx.
@@ -277,6 +437,7 @@ mod test {
             &None,
             indent,
             &WhitespaceDetector::default(),
+            &WrapMode::Greedy,
         );
 
         let expected = vec![sentence];
@@ -284,6 +445,142 @@ mod test {
         assert_eq!(expected, wrapped);
     }
 
+    #[test]
+    fn wrapping_long_sentence_at_clause_boundaries() {
+        let sentence = "if a sentence is long, we break it at commas; and before conjunctions or \
+                        after dashes — and only if nothing else works do we wrap greedily";
+        let wrapped = wrap_long_line_and_collapse_inline_whitespace(
+            sentence,
+            0,
+            &Some(30),
+            "  ",
+            &WhitespaceDetector::default(),
+            &WrapMode::Sembr,
+        );
+
+        let expected = vec![
+            "if a sentence is long,",
+            "  we break it at commas;",
+            "  and before conjunctions",
+            "  or after dashes —",
+            "  and only if nothing else",
+            "  works do we wrap greedily",
+        ];
+
+        assert_eq!(expected, wrapped);
+    }
+
+    #[test]
+    fn not_wrapping_at_clause_boundaries_if_sentence_fits() {
+        let sentence = "short, and sweet";
+        let wrapped = wrap_long_line_and_collapse_inline_whitespace(
+            sentence,
+            1,
+            &Some(30),
+            "  ",
+            &WhitespaceDetector::default(),
+            &WrapMode::Sembr,
+        );
+
+        assert_eq!(vec!["  short, and sweet"], wrapped);
+    }
+
+    #[test]
+    fn wrapping_long_sentence_optimally() {
+        let sentence = "aaa bb cc ddddd e";
+        let wrap = |mode| {
+            wrap_long_line_and_collapse_inline_whitespace(
+                sentence,
+                0,
+                &Some(6),
+                "",
+                &WhitespaceDetector::default(),
+                mode,
+            )
+        };
+
+        // Greedy wrapping leaves a lot of space on the second line and a widow on the last one.
+        assert_eq!(vec!["aaa bb", "cc", "ddddd", "e"], wrap(&WrapMode::Greedy));
+        // Optimal wrapping cannot avoid the widow here because "ddddd e" does not fit.
+        assert_eq!(vec!["aaa", "bb cc", "ddddd", "e"], wrap(&WrapMode::Optimal));
+    }
+
+    #[test]
+    fn wrapping_long_sentence_optimally_with_widow_control() {
+        let sentence = "aa bb cc dd ee";
+        let wrap = |mode| {
+            wrap_long_line_and_collapse_inline_whitespace(
+                sentence,
+                1,
+                &Some(13),
+                "  ",
+                &WhitespaceDetector::default(),
+                mode,
+            )
+        };
+
+        assert_eq!(vec!["  aa bb cc dd", "  ee"], wrap(&WrapMode::Greedy));
+        assert_eq!(vec!["  aa bb cc", "  dd ee"], wrap(&WrapMode::Optimal));
+    }
+
+    #[test]
+    fn wrapping_long_sentence_optimally_with_overlong_lines() {
+        let sentence = "aaa bb cc ddddddddd - e ff gg";
+        let wrap = |mode| {
+            wrap_long_line_and_collapse_inline_whitespace(
+                sentence,
+                0,
+                &Some(8),
+                "",
+                &WhitespaceDetector::default(),
+                mode,
+            )
+        };
+
+        // The dash must not start a line, which makes one line unavoidably too long.
+        assert_eq!(
+            vec!["aaa bb", "cc", "ddddddddd -", "e ff gg"],
+            wrap(&WrapMode::Greedy)
+        );
+        assert_eq!(
+            vec!["aaa", "bb cc", "ddddddddd -", "e ff gg"],
+            wrap(&WrapMode::Optimal)
+        );
+    }
+
+    #[test]
+    fn not_starting_lines_with_block_elements() {
+        let sentence = "it happened in version 2. Then + added # and - or > and [x]: too";
+        for mode in [WrapMode::Greedy, WrapMode::Sembr, WrapMode::Optimal] {
+            let wrapped = wrap_long_line_and_collapse_inline_whitespace(
+                sentence,
+                0,
+                &Some(22),
+                "",
+                &WhitespaceDetector::default(),
+                &mode,
+            );
+            assert!(
+                wrapped
+                    .iter()
+                    .all(|el| !starts_block_element(el.split(' ').next().unwrap_or(""))),
+                "{:?}",
+                wrapped
+            );
+            assert_eq!(sentence, wrapped.join(" "));
+        }
+        // Lines are extended beyond the maximum width if there is no other option.
+        let wrapped = wrap_long_line_and_collapse_inline_whitespace(
+            "some text - + 1. > and more",
+            0,
+            &Some(10),
+            "",
+            &WhitespaceDetector::default(),
+            &WrapMode::Greedy,
+        );
+        assert_eq!(vec!["some", "text - + 1. >", "and more"], wrapped);
+    }
+
     #[test]
     fn adding_linebreaks_after_sentences() {
         let ranges = vec![
@@ -306,7 +603,7 @@ mod test {
         );
         let detector = BreakDetector::new("", "", false, ".", CFG_FOR_TESTS);
 
-        let wrapped = add_linebreaks_and_wrap(ranges, &None, &detector, &text);
+        let wrapped = add_linebreaks_and_wrap(ranges, &None, &WrapMode::Greedy, &detector, &text);
 
         // Whitespace at the start of a range is also merged into one space. Not sure if that makes
         // sense but it does not appear to be relevant in practice, probably due to the way we
@@ -327,7 +624,7 @@ mod test {
         let text = String::from("Some text. It contains sentences.");
         let detector = BreakDetector::new("TEXT.", "", false, ".", CFG_FOR_TESTS);
 
-        let wrapped = add_linebreaks_and_wrap(ranges, &None, &detector, &text);
+        let wrapped = add_linebreaks_and_wrap(ranges, &None, &WrapMode::Greedy, &detector, &text);
 
         let expected = String::from("Some text. It contains sentences.");
         assert_eq!(expected, wrapped);